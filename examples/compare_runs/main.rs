@@ -0,0 +1,46 @@
+use bevy_integrator::{compare::compare_runs, recorder::read_recorded_data};
+
+// compare two recorded runs in the data folder, e.g. after changing the solver or step size
+// cargo run --example spring_mass -- --output reference.db
+// cargo run --example spring_mass -- --output other.db
+// cargo run --example compare_runs -- reference.db other.db 1e-3
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        println!("usage: compare_runs <reference.db> <other.db> [tolerance]");
+        return;
+    }
+    let tolerance = match args.get(3).map(|arg| arg.parse::<f32>()) {
+        None => 1e-3,
+        Some(Ok(tolerance)) => tolerance,
+        Some(Err(error)) => {
+            eprintln!("Invalid tolerance {}: {}", args[3], error);
+            return;
+        }
+    };
+
+    let folder_name = "./data";
+    let read = |file_name: &str| {
+        let full_path = format!("{}/{}", folder_name, file_name);
+        read_recorded_data(&full_path)
+            .map_err(|error| eprintln!("Could not read {}: {}", full_path, error))
+            .ok()
+    };
+    let (Some(reference), Some(other)) = (read(&args[1]), read(&args[2])) else {
+        return;
+    };
+
+    let comparison = compare_runs(&reference, &other, tolerance);
+    print!("{}", comparison);
+    if comparison.within_tolerance() {
+        println!("The runs match within the tolerance");
+    } else {
+        println!("The runs differ");
+    }
+
+    let report_path = format!("{}/comparison.txt", folder_name);
+    match comparison.write_report(&report_path) {
+        Ok(()) => println!("Report written to {}", report_path),
+        Err(error) => eprintln!("Could not write the report to {}: {}", report_path, error),
+    }
+}
@@ -1,7 +1,13 @@
 use bevy::prelude::*;
 use std::ops::{Add, Mul};
 
-use bevy_integrator::integrator::Stateful;
+use bevy_integrator::{
+    contact::GroundContact,
+    debug_draw::{DebugVectors, VectorKind},
+    integrator::Stateful,
+    interpolation::StateTransform,
+    playback::Replayable,
+};
 
 // this is an example of a stateful component that can be integrated in the physics engine
 #[derive(Component, Debug)]
@@ -96,3 +102,42 @@ pub fn bevy_joint_positions(mut joint_transform_query: Query<(&mut Joint, &mut T
         transform.translation = Vec3::new(0., 0., joint.position);
     }
 }
+
+impl Replayable for Joint {
+    fn set_recorded_state(&mut self, state: f32, dstate: f32) {
+        // the recorder stores position as the state and velocity as the state derivative
+        self.position = state;
+        self.velocity = dstate;
+    }
+}
+
+impl GroundContact for Joint {
+    fn contact_points(&self) -> Vec<(Vec3, Vec3)> {
+        // bottom face of the unit cube, the joint moves along Z
+        vec![(
+            Vec3::new(0., 0., self.position - 0.5),
+            Vec3::new(0., 0., self.velocity),
+        )]
+    }
+
+    fn add_contact_force(&mut self, _point: usize, force: Vec3) {
+        self.force += force.z;
+    }
+}
+
+impl DebugVectors for Joint {
+    fn debug_vector(&self, kind: VectorKind) -> Option<Vec3> {
+        let value = match kind {
+            VectorKind::Force => self.force,
+            VectorKind::Velocity => self.velocity,
+            VectorKind::Acceleration => self.acceleration,
+        };
+        Some(Vec3::new(0., 0., value))
+    }
+}
+
+impl StateTransform for Joint {
+    fn state_transform(state: &JointState, transform: &mut Transform) {
+        transform.translation = Vec3::new(0., 0., state.position);
+    }
+}
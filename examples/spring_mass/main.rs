@@ -4,12 +4,27 @@ pub mod model;
 use joint::{bevy_joint_positions, calculate_acceleration, Joint};
 use model::{apply_gravity, damping_force, spring_force};
 
-use bevy::prelude::*;
+use bevy::{prelude::*, render::view::VisibilitySystems, transform::TransformSystem};
 use bevy_integrator::{
     camera_az_el::{self, camera_builder},
+    camera_path::camera_path_system,
+    contact::{contact_inputs_changed, ground_contact},
+    debug_draw::DebugVectorsPlugin,
+    hud::HudPlugin,
     integrator::{
-        initialize_state, integrator_schedule, PhysicsSchedule, PhysicsScheduleExt, Solver,
+        apply_solver_changes, initialize_state, integrator_schedule, solver_controls, DenseOutput,
+        PhysicsSchedule, PhysicsScheduleExt, SetSolver, SetTimeStep, Solver,
     },
+    interpolation::{dense_output_transforms, interpolate_transforms},
+    picking::{pick_on_double_click, Picked},
+    playback::{playback_controls, playback_system, Playback},
+    plot::PlotPlugin,
+    recorder::{
+        commit_recorder, initialize_recorder, recorded_data_loader, recorder_builder,
+        recorder_system,
+    },
+    telemetry::TelemetryPlugin,
+    trail::TrailPlugin,
 };
 
 // set a larger timestep if the animation lags
@@ -19,41 +34,141 @@ const FIXED_TIMESTEP: f32 = 0.002; // 0.002s => 500 fps (starts lagging around 0
 
 // Main function
 fn main() {
-    let mut physics_schedule = Schedule::new();
-    physics_schedule.add_physics_systems::<Joint, _, _, _>(
-        (),
-        (spring_force, damping_force, apply_gravity),
-        (calculate_acceleration,),
-    );
-
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                resolution: (1200., 900.).into(),
-                title: "integrator".to_string(),
-                resizable: true,
-                ..default()
-            }),
+    // run with `--replay` to play back the last recorded run instead of simulating
+    let replay = std::env::args().any(|arg| arg == "--replay");
+    // run with `--telemetry` to stream the recorded channels on localhost:7878
+    let telemetry = std::env::args().any(|arg| arg == "--telemetry");
+    // run with `--plot` to show live plots of the recorded channels (toggle with P)
+    let plot = std::env::args().any(|arg| arg == "--plot");
+    // run with `--dense` to draw the model from the dense output between physics steps
+    let dense = std::env::args().any(|arg| arg == "--dense");
+    // run with `--record-camera` to also record the camera view, e.g. to replay it with the run
+    let record_camera = std::env::args().any(|arg| arg == "--record-camera");
+    // run with `--output <file>` to record to (or replay from) data/<file>, e.g. to compare runs
+    let args: Vec<String> = std::env::args().collect();
+    let output = args
+        .iter()
+        .position(|arg| arg == "--output")
+        .and_then(|index| args.get(index + 1))
+        .map_or("dummy.db", |file_name| file_name.as_str());
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            resolution: (1200., 900.).into(),
+            title: "integrator".to_string(),
+            resizable: true,
+            ..default()
+        }),
+        ..default()
+    }))
+    .add_startup_system(camera_builder(
+        Vec3 {
+            x: 0.,
+            y: 0.,
+            z: 2.,
+        },
+        -10.0_f32.to_radians(),
+        10.0_f32.to_radians(),
+        10.,
+        camera_az_el::UpDirection::Z,
+    ))
+    .add_system(camera_az_el::az_el_camera)
+    .add_system(camera_az_el::camera_view_shortcuts) // 1-8: preset views, ctrl+s/ctrl+l: save/load view
+    .add_event::<camera_az_el::FrameEntities>()
+    .add_system(camera_az_el::frame_on_key::<Joint>) // F: frame the model
+    .add_system(camera_az_el::frame_entities)
+    .add_event::<Picked>()
+    .add_system(pick_on_double_click) // double click: focus the camera on the clicked body
+    .add_system(camera_path_system.after(camera_az_el::az_el_camera)) // play CameraPath keyframes
+    .add_system(
+        camera_az_el::camera_follow
+            .in_base_set(CoreSet::PostUpdate)
+            .after(TransformSystem::TransformPropagate)
+            .before(VisibilitySystems::UpdateProjectionFrusta),
+    ) // follow entities with a CameraTarget
+    .add_startup_system(model::setup) // setup the model and environment
+    .add_plugin(DebugVectorsPlugin::<Joint>::default()) // F1: force, F2: velocity, F3: acceleration arrows
+    .add_plugin(TrailPlugin) // draw the trails of entities with a Trail
+    .add_plugin(HudPlugin::default()); // H: simulation time, solver and performance overlay
+
+    if replay {
+        app.add_startup_system(recorded_data_loader(output)) // load the recorded run
+            .init_resource::<Playback>()
+            .add_system(playback_system::<Joint>.before(bevy_joint_positions)) // drive the joints from the recorded data
+            .add_system(bevy_joint_positions) // update the bevy joint positions
+            .add_system(playback_controls); // space: pause, up/down: speed, left/right: scrub while paused, backspace: restart
+    } else {
+        let mut physics_schedule = Schedule::new();
+        physics_schedule.add_physics_systems::<Joint, _, _, _>(
+            (),
+            (
+                spring_force,
+                damping_force,
+                apply_gravity,
+                ground_contact::<Joint>, // keep the cube above the ground
+            ),
+            (calculate_acceleration,),
+        );
+
+        app.add_startup_system(initialize_state::<Joint>.in_base_set(StartupSet::PostStartup)) // setup the car model and environment
+            .insert_resource(FixedTime::new_from_secs(FIXED_TIMESTEP)) // set the fixed timestep
+            .add_schedule(PhysicsSchedule, physics_schedule) // add the physics schedule
+            .insert_resource(Solver::RK4) // set the solver to use
+            .add_system(integrator_schedule::<Joint>.in_schedule(CoreSchedule::FixedUpdate)) // run the physics schedule in the fixed timestep loop
+            .add_system(contact_inputs_changed.in_base_set(CoreSet::Last)) // restart the integrator when the ground changes
+            .add_event::<SetSolver>()
+            .add_event::<SetTimeStep>()
+            .add_system(solver_controls) // M: next solver, [ and ]: halve and double the step size
+            .add_system(apply_solver_changes.after(solver_controls))
+            .add_startup_system(recorder_builder(output)) // record the run so it can be replayed
+            .add_startup_system(
+                model::add_recorder_channels
+                    .in_base_set(StartupSet::PostStartup)
+                    .before(initialize_recorder::<Joint>),
+            ) // record forces alongside the states
+            .add_startup_system(initialize_recorder::<Joint>.in_base_set(StartupSet::PostStartup))
+            .add_system(
+                recorder_system::<Joint>
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .after(integrator_schedule::<Joint>),
+            ) // record every step at the simulation time
+            .add_system(commit_recorder.in_base_set(CoreSet::Last));
+
+        if record_camera {
+            app.add_startup_system(
+                model::add_recorder_camera_channels
+                    .in_base_set(StartupSet::PostStartup)
+                    .before(initialize_recorder::<Joint>),
+            ); // camera_* channels, left out by default so runs can be compared
+        }
+
+        if dense {
+            app.insert_resource(DenseOutput::<Joint>::default())
+                .add_system(dense_output_transforms::<Joint>)
+                .add_system(
+                    model::report_turning_points
+                        .in_schedule(CoreSchedule::FixedUpdate)
+                        .after(integrator_schedule::<Joint>),
+                ); // print the turning points of the cube
+        } else {
+            app.add_system(interpolate_transforms::<Joint>); // blend the last two physics steps
+        }
+    }
+
+    if telemetry {
+        app.add_plugin(TelemetryPlugin::default());
+    }
+
+    if plot {
+        app.add_plugin(PlotPlugin {
+            axes: vec![
+                vec!["cube_state".to_string()],
+                vec!["cube_dstate".to_string(), "cube_force".to_string()],
+            ],
             ..default()
-        }))
-        .add_startup_system(camera_builder(
-            Vec3 {
-                x: 0.,
-                y: 0.,
-                z: 2.,
-            },
-            -10.0_f32.to_radians(),
-            10.0_f32.to_radians(),
-            10.,
-            camera_az_el::UpDirection::Z,
-        ))
-        .add_system(camera_az_el::az_el_camera)
-        .add_startup_system(model::setup) // setup the model and environment
-        .add_startup_system(initialize_state::<Joint>.in_base_set(StartupSet::PostStartup)) // setup the car model and environment
-        .insert_resource(FixedTime::new_from_secs(FIXED_TIMESTEP)) // set the fixed timestep
-        .add_schedule(PhysicsSchedule, physics_schedule) // add the physics schedule
-        .insert_resource(Solver::RK4) // set the solver to use
-        .add_system(integrator_schedule::<Joint>.in_schedule(CoreSchedule::FixedUpdate)) // run the physics schedule in the fixed timestep loop
-        .add_system(bevy_joint_positions) // update the bevy joint positions
-        .run();
+        });
+    }
+
+    app.run();
 }
@@ -1,7 +1,14 @@
 use bevy::prelude::*;
 
 use crate::joint::Joint;
-use bevy_integrator::environment::build_environment;
+use bevy_integrator::{
+    camera_az_el::UpDirection,
+    camera_path::add_camera_channels,
+    environment::{build_environment, EnvironmentConfig},
+    integrator::{DenseOutput, StateMap},
+    recorder::Recorder,
+    trail::{SpeedColors, Trail},
+};
 
 pub fn spring_force(mut joint_query: Query<&mut Joint>) {
     for mut joint in joint_query.iter_mut() {
@@ -21,13 +28,51 @@ pub fn apply_gravity(mut joint_query: Query<&mut Joint>) {
     }
 }
 
+// print where the cube turns around, located between the physics steps on the dense output
+pub fn report_turning_points(
+    dense_output: Res<DenseOutput<Joint>>,
+    joint_query: Query<(Entity, &Joint)>,
+) {
+    for (entity, joint) in joint_query.iter() {
+        let velocity =
+            |states: &StateMap<Joint>| states.get(&entity).map_or(0., |state| state.velocity);
+        if let Some((t, states)) = dense_output.find_event(velocity, 1e-5) {
+            if let Some(state) = states.get(&entity) {
+                println!(
+                    "{} turns at t = {:.5} s, position {:.3} m",
+                    joint.name, t, state.position
+                );
+            }
+        }
+    }
+}
+
+pub fn add_recorder_channels(mut recorder: NonSendMut<Recorder>) {
+    recorder
+        .add_channel("force", |joint: &Joint| joint.force)
+        .add_channel("acceleration", |joint: &Joint| joint.acceleration);
+}
+
+// the camera view changes between runs of the same model, so it is only recorded on request
+pub fn add_recorder_camera_channels(mut recorder: NonSendMut<Recorder>) {
+    add_camera_channels(&mut recorder);
+}
+
 pub fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
     build_model(&mut commands, &mut meshes, &mut materials);
-    build_environment(&mut commands, &mut meshes, &mut materials);
+    build_environment(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &EnvironmentConfig {
+            up_direction: UpDirection::Z, // matches the camera
+            ..default()
+        },
+    );
 }
 
 pub fn build_model(
@@ -49,4 +94,9 @@ pub fn build_model(
         mass: 1.,
         name: "cube".to_string(),
     });
+    entity.insert(Trail::new(3.).with_speed_colors(SpeedColors {
+        slow: Color::BLUE,
+        fast: Color::YELLOW,
+        max_speed: 5.,
+    }));
 }
@@ -0,0 +1,22 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+};
+
+// print the telemetry stream of a running simulation, e.g.
+// cargo run --example spring_mass -- --telemetry
+// cargo run --example telemetry_client -- cube_state,cube_force 10
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let channels = args.get(1).map_or("*", |arg| arg.as_str());
+    let rate = args.get(2).map_or("60", |arg| arg.as_str());
+
+    let mut stream = TcpStream::connect("127.0.0.1:7878").unwrap();
+    writeln!(stream, "subscribe {}", channels).unwrap();
+    writeln!(stream, "rate {}", rate).unwrap();
+
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        println!("{}", line.unwrap());
+    }
+}
@@ -2,7 +2,13 @@
 use bevy::{
     input::mouse::{MouseMotion, MouseWheel},
     prelude::*,
-    render::camera::Projection,
+    render::{
+        camera::{
+            OrthographicProjection, PerspectiveProjection, Projection, RenderTarget, ScalingMode,
+        },
+        primitives::Aabb,
+    },
+    window::{PrimaryWindow, WindowRef},
 };
 use std::f32::consts::PI;
 
@@ -16,6 +22,25 @@ pub enum UpDirection {
     Z,
 }
 
+impl UpDirection {
+    pub fn vector(&self) -> Vec3 {
+        match self {
+            UpDirection::X => Vec3::X,
+            UpDirection::Y => Vec3::Y,
+            UpDirection::Z => Vec3::Z,
+        }
+    }
+}
+
+// Orbit rotates the camera around the focus point. Fly looks around from the camera position and
+// moves it with the fly keys. Both modes use the same azimuth and elevation, with the focus point
+// `radius` in front of the camera, so switching modes doesn't move the view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+
 #[derive(Component)]
 pub struct AzElCamera {
     pub focus: Vec3,
@@ -23,6 +48,11 @@ pub struct AzElCamera {
     pub up_direction: UpDirection,
     pub azimuth: f32,
     pub elevation: f32,
+    pub smoothing: f32, // time constant in seconds for user input, 0 moves the camera instantly
+    pub target: Option<CameraView>, // view the camera is moving towards when smoothing
+    pub animation: Option<CameraAnimation>,
+    pub presets: Vec<(String, CameraView)>, // selected with the number keys, see camera_view_shortcuts
+    pub mode: CameraMode,
 }
 
 impl Default for AzElCamera {
@@ -33,87 +63,914 @@ impl Default for AzElCamera {
             up_direction: UpDirection::Y,
             azimuth: 0.,
             elevation: 0.,
+            smoothing: 0.,
+            target: None,
+            animation: None,
+            presets: Vec::new(),
+            mode: CameraMode::Orbit,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraView {
+    pub focus: Vec3,
+    pub azimuth: f32,
+    pub elevation: f32,
+    pub radius: f32,
+}
+
+impl CameraView {
+    // position of the camera for this view
+    pub fn eye(&self, up_direction: &UpDirection) -> Vec3 {
+        let rotation = az_el_rotation(self.azimuth, self.elevation, up_direction);
+        az_el_translation(self.focus, rotation, self.radius)
+    }
+
+    pub fn lerp(&self, other: &CameraView, s: f32) -> CameraView {
+        CameraView {
+            focus: self.focus.lerp(other.focus, s),
+            azimuth: self.azimuth + (other.azimuth - self.azimuth) * s,
+            elevation: self.elevation + (other.elevation - self.elevation) * s,
+            // interpolate the radius geometrically so zooming feels uniform
+            radius: self.radius * (other.radius / self.radius).powf(s),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn ease(&self, s: f32) -> f32 {
+        let s = s.clamp(0., 1.);
+        match self {
+            Easing::Linear => s,
+            Easing::EaseIn => s * s * s,
+            Easing::EaseOut => 1. - (1. - s).powi(3),
+            Easing::EaseInOut => s * s * (3. - 2. * s),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CameraAnimation {
+    pub from: CameraView,
+    pub to: CameraView,
+    pub duration: f32,
+    pub elapsed: f32,
+    pub easing: Easing,
+}
+
+impl AzElCamera {
+    pub fn view(&self) -> CameraView {
+        CameraView {
+            focus: self.focus,
+            azimuth: self.azimuth,
+            elevation: self.elevation,
+            radius: self.radius,
+        }
+    }
+
+    // the camera transform for the current view
+    pub fn transform(&self) -> Transform {
+        let rotation = az_el_rotation(self.azimuth, self.elevation, &self.up_direction);
+        Transform {
+            translation: az_el_translation(self.focus, rotation, self.radius),
+            rotation,
+            ..default()
+        }
+    }
+
+    pub fn set_view(&mut self, view: &CameraView) {
+        self.focus = view.focus;
+        self.azimuth = view.azimuth;
+        self.elevation = view.elevation;
+        self.radius = view.radius;
+    }
+
+    // move the camera to a new view over `duration` seconds
+    pub fn animate_to(&mut self, view: CameraView, duration: f32) {
+        self.animate_to_with_easing(view, duration, Easing::EaseInOut);
+    }
+
+    pub fn animate_to_with_easing(&mut self, mut view: CameraView, duration: f32, easing: Easing) {
+        // take the short way around
+        let turns = ((view.azimuth - self.azimuth) / (2. * PI)).round();
+        view.azimuth -= turns * 2. * PI;
+        self.target = None;
+        self.animation = Some(CameraAnimation {
+            from: self.view(),
+            to: view,
+            duration,
+            elapsed: 0.,
+            easing,
+        });
+    }
+
+    // front, back, left, right, top, bottom and isometric views of the focus point at the current
+    // radius. Azimuth and elevation are relative to the up direction (see az_el_rotation), so these
+    // are consistent for any UpDirection.
+    pub fn standard_views(&self) -> Vec<(String, CameraView)> {
+        let view = |azimuth: f32, elevation: f32| CameraView {
+            focus: self.focus,
+            azimuth,
+            elevation,
+            radius: self.radius,
+        };
+        vec![
+            ("front".to_string(), view(0., 0.)),
+            ("right".to_string(), view(PI / 2., 0.)),
+            ("back".to_string(), view(PI, 0.)),
+            ("left".to_string(), view(-PI / 2., 0.)),
+            ("top".to_string(), view(0., PI / 2.)),
+            ("bottom".to_string(), view(0., -PI / 2.)),
+            (
+                "isometric".to_string(),
+                view(PI / 4., (1. / 2_f32.sqrt()).atan()),
+            ),
+        ]
+    }
+
+    pub fn add_preset(&mut self, name: &str, view: CameraView) {
+        self.presets.retain(|(preset_name, _)| preset_name != name);
+        self.presets.push((name.to_string(), view));
+    }
+
+    pub fn preset(&self, name: &str) -> Option<CameraView> {
+        self.presets
+            .iter()
+            .find(|(preset_name, _)| preset_name == name)
+            .map(|(_, view)| *view)
+    }
+
+    // save the current view to a text file: "focus_x focus_y focus_z azimuth elevation radius"
+    pub fn save_view(&self, path: &str) -> std::io::Result<()> {
+        let view = self.view();
+        let contents = format!(
+            "{} {} {} {} {} {}\n",
+            view.focus.x, view.focus.y, view.focus.z, view.azimuth, view.elevation, view.radius
+        );
+        std::fs::write(path, contents)
+    }
+
+    pub fn load_view(path: &str) -> std::io::Result<CameraView> {
+        let contents = std::fs::read_to_string(path)?;
+        let values = contents
+            .split_whitespace()
+            .map(|value| value.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let [x, y, z, azimuth, elevation, radius] = values[..] else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "expected 6 values: focus_x focus_y focus_z azimuth elevation radius",
+            ));
+        };
+        Ok(CameraView {
+            focus: Vec3::new(x, y, z),
+            azimuth,
+            elevation,
+            radius,
+        })
+    }
+
+    // Switch between perspective and orthographic projection, keeping the apparent size of objects
+    // at the focus point. The orthographic projection uses a fixed vertical size (scaled by
+    // `scale`), so its framing doesn't depend on the window size.
+    pub fn toggle_projection(&mut self, projection: &mut Projection) {
+        let new_projection = match projection {
+            Projection::Perspective(perspective) => {
+                // visible height at the focus point
+                let height = 2. * self.radius * (perspective.fov / 2.).tan();
+                Projection::Orthographic(OrthographicProjection {
+                    scaling_mode: ScalingMode::FixedVertical(1.),
+                    scale: height,
+                    far: perspective.far,
+                    ..default()
+                })
+            }
+            Projection::Orthographic(orthographic) => {
+                let perspective = PerspectiveProjection {
+                    far: orthographic.far,
+                    ..default()
+                };
+                // move the camera so the focus point has the same visible height
+                let height = orthographic.area.height();
+                self.radius = (height / (2. * (perspective.fov / 2.).tan())).max(0.05);
+                Projection::Perspective(perspective)
+            }
+        };
+        *projection = new_projection;
+        self.target = None;
+        self.animation = None;
+    }
+
+    // advance smoothing and animations, returns true if the view changed
+    fn update_motion(&mut self, dt: f32) -> bool {
+        if let Some(mut animation) = self.animation {
+            animation.elapsed += dt;
+            let s = if animation.duration > 0. {
+                animation.elapsed / animation.duration
+            } else {
+                1.
+            };
+            let view = animation.from.lerp(&animation.to, animation.easing.ease(s));
+            self.set_view(&view);
+            self.animation = if s < 1. { Some(animation) } else { None };
+            return true;
+        }
+        if let Some(target) = self.target {
+            let blend = if self.smoothing > 0. {
+                1. - (-dt / self.smoothing).exp()
+            } else {
+                1.
+            };
+            let view = self.view().lerp(&target, blend);
+            self.set_view(&view);
+            // stop once the remaining motion is negligible
+            let close = (view.focus - target.focus).length() < 1e-4 * target.radius
+                && (view.azimuth - target.azimuth).abs() < 1e-4
+                && (view.elevation - target.elevation).abs() < 1e-4
+                && (view.radius / target.radius - 1.).abs() < 1e-4;
+            if close {
+                self.set_view(&target);
+                self.target = None;
+            }
+            return true;
+        }
+        false
+    }
+}
+
+// Input bindings for the camera. Insert as a resource to change the bindings for all cameras, or
+// as a component on a camera to override them for that camera.
+#[derive(Component, Resource, Clone)]
+pub struct AzElCameraControls {
+    pub orbit_button: MouseButton,
+    pub pan_button: Option<MouseButton>,
+    pub pan_modifier: Option<KeyCode>, // hold to pan with the orbit button (for laptops)
+    pub orbit_keys: Option<[KeyCode; 4]>, // left, right, up, down
+    pub pan_keys: Option<[KeyCode; 4]>, // left, right, up, down
+    pub zoom_keys: Option<[KeyCode; 2]>, // in, out
+    pub projection_key: Option<KeyCode>, // toggle between perspective and orthographic
+    pub mode_key: Option<KeyCode>,     // toggle between orbit and fly mode
+    pub fly_keys: Option<[KeyCode; 6]>, // forward, back, left, right, up, down
+    pub fly_boost_key: Option<KeyCode>, // hold to fly faster, must differ from pan_modifier
+    pub fly_speed: f32,                // units per second
+    pub orbit_sensitivity: f32,
+    pub pan_sensitivity: f32,
+    pub zoom_sensitivity: f32, // fraction of the radius per scroll line
+    pub key_orbit_speed: f32,  // radians per second
+    pub key_pan_speed: f32,    // window widths per second
+    pub key_zoom_speed: f32,   // scroll lines per second
+    pub invert_orbit_x: bool,
+    pub invert_orbit_y: bool,
+    pub invert_pan: bool,
+    pub invert_zoom: bool,
+}
+
+impl Default for AzElCameraControls {
+    fn default() -> Self {
+        AzElCameraControls {
+            orbit_button: MouseButton::Left,
+            pan_button: Some(MouseButton::Middle),
+            pan_modifier: Some(KeyCode::LShift),
+            orbit_keys: None,
+            pan_keys: None,
+            zoom_keys: None,
+            projection_key: Some(KeyCode::O),
+            mode_key: Some(KeyCode::Tab),
+            fly_keys: Some([
+                KeyCode::W,
+                KeyCode::S,
+                KeyCode::A,
+                KeyCode::D,
+                KeyCode::E,
+                KeyCode::Q,
+            ]),
+            fly_boost_key: Some(KeyCode::LAlt),
+            fly_speed: 5.,
+            orbit_sensitivity: 1.,
+            pan_sensitivity: 1.,
+            zoom_sensitivity: 0.2,
+            key_orbit_speed: 1.,
+            key_pan_speed: 0.5,
+            key_zoom_speed: 5.,
+            invert_orbit_x: false,
+            invert_orbit_y: false,
+            invert_pan: false,
+            invert_zoom: false,
+        }
+    }
+}
+
+fn key_axis(input_keyboard: &Input<KeyCode>, keys: &Option<[KeyCode; 4]>) -> Vec2 {
+    let mut axis = Vec2::ZERO;
+    if let Some([left, right, up, down]) = keys {
+        if input_keyboard.pressed(*left) {
+            axis.x -= 1.;
+        }
+        if input_keyboard.pressed(*right) {
+            axis.x += 1.;
+        }
+        if input_keyboard.pressed(*up) {
+            axis.y -= 1.;
+        }
+        if input_keyboard.pressed(*down) {
+            axis.y += 1.;
+        }
+    }
+    axis
+}
+
+// The camera (and cursor position within its viewport) under the cursor. The cursor position has
+// its origin at the top left of the viewport. Overlapping viewports are resolved by camera order.
+pub fn camera_under_cursor<'a>(
+    cameras: impl Iterator<Item = (Entity, &'a Camera)>,
+    windows: &Query<(Entity, &Window)>,
+    primary_window: &Query<Entity, With<PrimaryWindow>>,
+) -> Option<(Entity, Vec2)> {
+    let mut hovered: Option<(Entity, Vec2, isize)> = None;
+    for (entity, camera) in cameras {
+        let Some(window_entity) = camera_window(camera, primary_window) else {
+            continue;
+        };
+        let Ok((_, window)) = windows.get(window_entity) else {
+            continue;
+        };
+        let Some(cursor) = window.cursor_position() else {
+            continue;
+        };
+        // the window cursor position has its origin at the bottom left
+        let cursor = Vec2::new(cursor.x, window.height() - cursor.y);
+        let Some((min, max)) = camera.logical_viewport_rect() else {
+            continue;
+        };
+        let inside = cursor.cmpge(min).all() && cursor.cmplt(max).all();
+        if inside && !matches!(hovered, Some((_, _, order)) if order >= camera.order) {
+            hovered = Some((entity, cursor - min, camera.order));
         }
     }
+    hovered.map(|(entity, cursor, _)| (entity, cursor))
+}
+
+// Keyboard input goes to the given camera (e.g. the one under the cursor), or to the cameras of the
+// focused window when there is none.
+pub fn receives_keyboard(
+    entity: Entity,
+    camera: &Camera,
+    active_camera: Option<Entity>,
+    windows: &Query<(Entity, &Window)>,
+    primary_window: &Query<Entity, With<PrimaryWindow>>,
+) -> bool {
+    match active_camera {
+        Some(active) => active == entity,
+        None => camera_window(camera, primary_window)
+            .and_then(|window| windows.get(window).ok())
+            .is_some_and(|(_, window)| window.focused),
+    }
+}
+
+fn camera_window(
+    camera: &Camera,
+    primary_window: &Query<Entity, With<PrimaryWindow>>,
+) -> Option<Entity> {
+    match &camera.target {
+        RenderTarget::Window(WindowRef::Primary) => primary_window.get_single().ok(),
+        RenderTarget::Window(WindowRef::Entity(entity)) => Some(*entity),
+        RenderTarget::Image(_) => None,
+    }
 }
 
+// Mouse input only goes to the camera under the cursor (and stays with it while a button is held).
+// Keyboard input goes to the same camera, or to the cameras of the focused window when the cursor
+// isn't over any camera.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn az_el_camera(
-    windows: Query<&mut Window>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
     mut ev_motion: EventReader<MouseMotion>,
     mut ev_scroll: EventReader<MouseWheel>,
     input_mouse: Res<Input<MouseButton>>,
-    mut query: Query<(&mut AzElCamera, &mut Transform, &Projection)>,
+    input_keyboard: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    default_controls: Option<Res<AzElCameraControls>>,
+    mut active_camera: Local<Option<Entity>>,
+    mut query: Query<(
+        Entity,
+        &Camera,
+        &mut AzElCamera,
+        &mut Transform,
+        &mut Projection,
+        Option<&AzElCameraControls>,
+    )>,
 ) {
-    // change input mapping for orbit and panning here
-    let orbit_button = MouseButton::Left;
-    let pan_button = MouseButton::Middle;
-
-    let mut pan = Vec2::ZERO;
-    let mut rotation_move = Vec2::ZERO;
-    let mut scroll = 0.0;
-
-    // Handle user input here
-    if input_mouse.pressed(orbit_button) {
-        for ev in ev_motion.iter() {
-            rotation_move += ev.delta;
-        }
-    } else if input_mouse.pressed(pan_button) {
-        // Pan only if we're not rotating at the moment
-        for ev in ev_motion.iter() {
-            pan += ev.delta;
-        }
+    let mut mouse_motion = Vec2::ZERO;
+    let mut mouse_scroll = 0.0;
+    for ev in ev_motion.iter() {
+        mouse_motion += ev.delta;
     }
     for ev in ev_scroll.iter() {
-        scroll += ev.y;
+        mouse_scroll += ev.y;
     }
+    let default_controls = default_controls.map_or_else(AzElCameraControls::default, |c| c.clone());
+    let dt = time.delta_seconds();
+
+    // route input to the camera under the cursor
+    let hovered = camera_under_cursor(
+        query.iter().map(|(entity, camera, ..)| (entity, camera)),
+        &windows,
+        &primary_window,
+    )
+    .map(|(entity, _)| entity);
+    if input_mouse.get_pressed().next().is_none() || active_camera.is_none() {
+        *active_camera = hovered;
+    }
+    let no_buttons = Input::<MouseButton>::default();
+    let no_keys = Input::<KeyCode>::default();
 
     // update cameras
-    for (mut az_el, mut transform, projection) in query.iter_mut() {
+    for (entity, camera, mut az_el, mut transform, mut projection, controls) in query.iter_mut() {
+        let controls = controls.unwrap_or(&default_controls);
+
+        let mouse_active = *active_camera == Some(entity);
+        let keyboard_active =
+            receives_keyboard(entity, camera, *active_camera, &windows, &primary_window);
+        let (input_mouse, mouse_motion, mouse_scroll) = if mouse_active {
+            (&*input_mouse, mouse_motion, mouse_scroll)
+        } else {
+            (&no_buttons, Vec2::ZERO, 0.)
+        };
+        let input_keyboard = if keyboard_active {
+            &*input_keyboard
+        } else {
+            &no_keys
+        };
+
+        // scale mouse motion by the size of this camera's viewport
+        let Some(window) = camera.logical_viewport_size() else {
+            continue;
+        };
+
+        if let Some(key) = controls.projection_key {
+            if input_keyboard.just_pressed(key) {
+                az_el.toggle_projection(&mut projection);
+                transform.translation =
+                    az_el_translation(az_el.focus, transform.rotation, az_el.radius);
+            }
+        }
+
+        if let Some(key) = controls.mode_key {
+            if input_keyboard.just_pressed(key) {
+                az_el.mode = match az_el.mode {
+                    CameraMode::Orbit => CameraMode::Fly,
+                    CameraMode::Fly => CameraMode::Orbit,
+                };
+            }
+        }
+        let fly = az_el.mode == CameraMode::Fly;
+
+        let mut pan = Vec2::ZERO;
+        let mut rotation_move = Vec2::ZERO;
+        let mut scroll = mouse_scroll;
+
+        // Handle user input here
+        let pan_modifier = controls
+            .pan_modifier
+            .is_some_and(|key| input_keyboard.pressed(key));
+        let pan_pressed = controls
+            .pan_button
+            .is_some_and(|button| input_mouse.pressed(button));
+        if input_mouse.pressed(controls.orbit_button) && !pan_modifier {
+            rotation_move += mouse_motion * controls.orbit_sensitivity;
+        } else if pan_pressed || (pan_modifier && input_mouse.pressed(controls.orbit_button)) {
+            // Pan only if we're not rotating at the moment
+            pan += mouse_motion * controls.pan_sensitivity;
+        }
+        let key_orbit = key_axis(input_keyboard, &controls.orbit_keys);
+        let key_pan = key_axis(input_keyboard, &controls.pan_keys);
+        if let Some([zoom_in, zoom_out]) = controls.zoom_keys {
+            if input_keyboard.pressed(zoom_in) {
+                scroll += controls.key_zoom_speed * dt;
+            }
+            if input_keyboard.pressed(zoom_out) {
+                scroll -= controls.key_zoom_speed * dt;
+            }
+        }
+        if controls.invert_zoom {
+            scroll = -scroll;
+        }
+
         let mut any_changes = false; // has anything changed?
 
-        if rotation_move.length_squared() > 0.0 {
+        // user input moves the target view when smoothing, otherwise the camera itself
+        let mut view = match az_el.target {
+            Some(target) if az_el.smoothing > 0. => target,
+            _ => az_el.view(),
+        };
+
+        if rotation_move.length_squared() > 0.0 || key_orbit.length_squared() > 0.0 {
             any_changes = true;
-            let window = get_primary_window_size(&windows);
-            let delta_x = rotation_move.x / window.x * PI * 2.0;
-            let delta_y = rotation_move.y / window.y * PI;
+            let mut delta_x = rotation_move.x / window.x * PI * 2.0;
+            let mut delta_y = rotation_move.y / window.y * PI;
+            delta_x += key_orbit.x * controls.key_orbit_speed * dt;
+            delta_y += key_orbit.y * controls.key_orbit_speed * dt;
+            if controls.invert_orbit_x {
+                delta_x = -delta_x;
+            }
+            if controls.invert_orbit_y {
+                delta_y = -delta_y;
+            }
 
-            az_el.azimuth -= delta_x;
-            az_el.elevation += delta_y;
+            if fly {
+                // look around from the camera position, so the focus moves instead of the camera
+                let eye = view.eye(&az_el.up_direction);
+                view.azimuth += delta_x;
+                view.elevation -= delta_y;
+                view.elevation = view.elevation.clamp(-PI / 2., PI / 2.);
+                let rotation = az_el_rotation(view.azimuth, view.elevation, &az_el.up_direction);
+                view.focus = eye - rotation * Vec3::new(0.0, 0.0, view.radius);
+            } else {
+                view.azimuth -= delta_x;
+                view.elevation += delta_y;
 
-            az_el.elevation = az_el.elevation.max(-PI / 2.).min(PI / 2.);
-            transform.rotation =
-                az_el_rotation(az_el.azimuth, az_el.elevation, &az_el.up_direction);
+                view.elevation = view.elevation.max(-PI / 2.).min(PI / 2.);
+            }
         }
 
-        if pan.length_squared() > 0.0 {
+        // ctrl is held for shortcuts like ctrl+s, which shouldn't also fly backwards
+        let ctrl = input_keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+        if fly && !ctrl {
+            let mut movement = Vec3::ZERO;
+            if let Some([forward, back, left, right, up, down]) = controls.fly_keys {
+                let rotation = az_el_rotation(view.azimuth, view.elevation, &az_el.up_direction);
+                let axes = [
+                    (forward, rotation * Vec3::NEG_Z),
+                    (back, rotation * Vec3::Z),
+                    (left, rotation * Vec3::NEG_X),
+                    (right, rotation * Vec3::X),
+                    (up, az_el.up_direction.vector()),
+                    (down, -az_el.up_direction.vector()),
+                ];
+                for (key, direction) in axes {
+                    if input_keyboard.pressed(key) {
+                        movement += direction;
+                    }
+                }
+            }
+            if movement.length_squared() > 0.0 {
+                any_changes = true;
+                let boost = controls
+                    .fly_boost_key
+                    .is_some_and(|key| input_keyboard.pressed(key));
+                let speed = controls.fly_speed * if boost { 4. } else { 1. };
+                // the focus moves with the camera
+                view.focus += movement.normalize() * speed * dt;
+            }
+        }
+
+        if pan.length_squared() > 0.0 || key_pan.length_squared() > 0.0 {
             any_changes = true;
             // make panning distance independent of resolution and FOV,
-            let window = get_primary_window_size(&windows);
-            if let Projection::Perspective(projection) = projection {
-                pan *= Vec2::new(projection.fov * projection.aspect_ratio, projection.fov) / window;
+            pan += key_pan * controls.key_pan_speed * window * dt;
+            if controls.invert_pan {
+                pan = -pan;
+            }
+            match projection.as_ref() {
+                Projection::Perspective(projection) => {
+                    pan *= Vec2::new(projection.fov * projection.aspect_ratio, projection.fov)
+                        / window;
+                    // make panning proportional to distance away from focus point
+                    pan *= view.radius;
+                }
+                Projection::Orthographic(projection) => {
+                    // move the focus with the cursor, the visible area doesn't depend on the radius
+                    pan *= Vec2::new(projection.area.width(), projection.area.height()) / window;
+                }
             }
             // translate by local axes
-            let mat = Mat3::from_quat(transform.rotation);
+            let rotation = az_el_rotation(view.azimuth, view.elevation, &az_el.up_direction);
+            let mat = Mat3::from_quat(rotation);
             let left = -mat.x_axis * pan.x;
             let up = mat.y_axis * pan.y;
-            // make panning proportional to distance away from focus point
-            let translation = (left + up) * az_el.radius;
-            az_el.focus += translation;
+            view.focus += left + up;
         }
 
         if scroll.abs() > 0.0 {
-            any_changes = true;
-            az_el.radius -= scroll * az_el.radius * 0.2;
-            // dont allow zoom to reach zero or you get stuck
-            az_el.radius = az_el.radius.max(0.05);
+            if let Projection::Orthographic(projection) = projection.as_mut() {
+                // moving an orthographic camera doesn't change the apparent size, zoom by scale
+                projection.scale -= scroll * projection.scale * controls.zoom_sensitivity;
+                projection.scale = projection.scale.max(1e-4);
+            } else if fly {
+                // move forward, keeping the focus distance
+                any_changes = true;
+                let rotation = az_el_rotation(view.azimuth, view.elevation, &az_el.up_direction);
+                view.focus +=
+                    rotation * Vec3::NEG_Z * scroll * view.radius * controls.zoom_sensitivity;
+            } else {
+                any_changes = true;
+                view.radius -= scroll * view.radius * controls.zoom_sensitivity;
+                // dont allow zoom to reach zero or you get stuck
+                view.radius = view.radius.max(0.05);
+            }
         }
 
         if any_changes {
+            // user input takes over from any running animation
+            az_el.animation = None;
+            if az_el.smoothing > 0. {
+                az_el.target = Some(view);
+            } else {
+                az_el.set_view(&view);
+            }
+        }
+
+        if az_el.update_motion(dt) || any_changes {
+            transform.rotation =
+                az_el_rotation(az_el.azimuth, az_el.elevation, &az_el.up_direction);
             transform.translation = az_el_translation(az_el.focus, transform.rotation, az_el.radius)
         }
     }
 }
 
+// Makes the camera focus follow an entity. Orbit and zoom work as usual around the target, and
+// panning moves the focus relative to the target (the pan is kept as an offset). Animations (e.g.
+// preset views) change the direction and distance, but the focus stays on the target.
+#[derive(Component)]
+pub struct CameraTarget {
+    pub entity: Entity,
+    pub offset: Vec3,
+    pub smoothing: f32, // time constant in seconds, 0 follows the target exactly
+    pub follow_axes: BVec3, // e.g. BVec3::new(true, true, false) to ignore vertical motion
+    applied_focus: Option<Vec3>,
+    animating: bool, // an animation moved the focus since the last update
+}
+
+impl CameraTarget {
+    pub fn new(entity: Entity) -> Self {
+        CameraTarget {
+            entity,
+            offset: Vec3::ZERO,
+            smoothing: 0.,
+            follow_axes: BVec3::TRUE,
+            applied_focus: None,
+            animating: false,
+        }
+    }
+
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn with_axes(mut self, follow_axes: BVec3) -> Self {
+        self.follow_axes = follow_axes;
+        self
+    }
+}
+
+// Reads the target's GlobalTransform of the current frame, so run it in CoreSet::PostUpdate after
+// TransformSystem::TransformPropagate and before the camera frusta are updated, i.e.
+// camera_follow
+//     .in_base_set(CoreSet::PostUpdate)
+//     .after(TransformSystem::TransformPropagate)
+//     .before(VisibilitySystems::UpdateProjectionFrusta)
+// The camera's GlobalTransform is updated here, so the camera must not have a parent.
+pub fn camera_follow(
+    time: Res<Time>,
+    targets: Query<&GlobalTransform, Without<CameraTarget>>,
+    mut query: Query<(
+        &mut AzElCamera,
+        &mut Transform,
+        &mut GlobalTransform,
+        &mut CameraTarget,
+    )>,
+) {
+    for (mut az_el, mut transform, mut global_transform, mut target) in query.iter_mut() {
+        let Ok(target_transform) = targets.get(target.entity) else {
+            continue;
+        };
+
+        // keep any panning since the last update as an offset from the target. The focus also
+        // moves while animating, including the last step of the animation
+        let animating = az_el.animation.is_some() || target.animating;
+        target.animating = az_el.animation.is_some();
+        if let Some(applied_focus) = target.applied_focus {
+            if !animating {
+                let pan = az_el.focus - applied_focus;
+                target.offset += pan;
+            }
+        }
+
+        let target_focus = target_transform.translation() + target.offset;
+        let desired_focus = Vec3::select(target.follow_axes, target_focus, az_el.focus);
+        let blend = if target.smoothing > 0. {
+            1. - (-time.delta_seconds() / target.smoothing).exp()
+        } else {
+            1.
+        };
+        let focus = az_el.focus;
+        az_el.focus = focus + (desired_focus - focus) * blend;
+        // move a smoothed target view along with the focus
+        let change = az_el.focus - focus;
+        if let Some(view) = az_el.target.as_mut() {
+            view.focus += change;
+        }
+        target.applied_focus = Some(az_el.focus);
+
+        transform.translation = az_el_translation(az_el.focus, transform.rotation, az_el.radius);
+        *global_transform = GlobalTransform::from(*transform);
+    }
+}
+
+const VIEW_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+const VIEW_FILE: &str = "./data/camera_view.txt";
+const VIEW_TRANSITION: f32 = 0.5; // seconds
+
+// number keys switch to the camera presets, ctrl+s saves the current view, ctrl+l loads it. The
+// shortcuts go to the camera under the cursor, like the other camera controls
+pub fn camera_view_shortcuts(
+    input_keyboard: Res<Input<KeyCode>>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    mut query: Query<(Entity, &Camera, &mut AzElCamera)>,
+) {
+    let ctrl = input_keyboard.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let hovered = camera_under_cursor(
+        query.iter().map(|(entity, camera, _)| (entity, camera)),
+        &windows,
+        &primary_window,
+    )
+    .map(|(entity, _)| entity);
+    for (entity, camera, mut az_el) in query.iter_mut() {
+        if !receives_keyboard(entity, camera, hovered, &windows, &primary_window) {
+            continue;
+        }
+        for (index, key) in VIEW_KEYS.iter().enumerate() {
+            if input_keyboard.just_pressed(*key) {
+                if let Some((_, view)) = az_el.presets.get(index) {
+                    let view = *view;
+                    az_el.animate_to(view, VIEW_TRANSITION);
+                }
+            }
+        }
+
+        if ctrl && input_keyboard.just_pressed(KeyCode::S) {
+            if let Some(folder) = std::path::Path::new(VIEW_FILE).parent() {
+                std::fs::create_dir_all(folder).ok();
+            }
+            match az_el.save_view(VIEW_FILE) {
+                Ok(()) => println!("Camera view saved to {}", VIEW_FILE),
+                Err(e) => println!("Failed to save camera view: {}", e),
+            }
+        }
+        if ctrl && input_keyboard.just_pressed(KeyCode::L) {
+            match AzElCamera::load_view(VIEW_FILE) {
+                Ok(view) => az_el.animate_to(view, VIEW_TRANSITION),
+                Err(e) => println!("Failed to load camera view: {}", e),
+            }
+        }
+    }
+}
+
+// Frame entities in an AzElCamera, or in all of them when `camera` is None. An empty list frames
+// every entity with a mesh bounding box.
+pub struct FrameEntities {
+    pub entities: Vec<Entity>,
+    pub camera: Option<Entity>,
+}
+
+const FRAME_KEY: KeyCode = KeyCode::F;
+const FRAME_MARGIN: f32 = 1.1; // leave a little space around the framed entities
+
+// send a FrameEntities event for all entities with component T when the frame key is pressed, to
+// the cameras that receive keyboard input (see receives_keyboard)
+pub fn frame_on_key<T: Component>(
+    input_keyboard: Res<Input<KeyCode>>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    cameras: Query<(Entity, &Camera), With<AzElCamera>>,
+    query: Query<Entity, (With<T>, With<Aabb>)>,
+    mut ev_frame: EventWriter<FrameEntities>,
+) {
+    if !input_keyboard.just_pressed(FRAME_KEY) {
+        return;
+    }
+    // an empty list would frame everything, including the ground, before the bounds are computed
+    let entities: Vec<Entity> = query.iter().collect();
+    if entities.is_empty() {
+        return;
+    }
+    let hovered =
+        camera_under_cursor(cameras.iter(), &windows, &primary_window).map(|(entity, _)| entity);
+    for (entity, camera) in cameras.iter() {
+        if receives_keyboard(entity, camera, hovered, &windows, &primary_window) {
+            ev_frame.send(FrameEntities {
+                entities: entities.clone(),
+                camera: Some(entity),
+            });
+        }
+    }
+}
+
+pub fn frame_entities(
+    mut ev_frame: EventReader<FrameEntities>,
+    bounds: Query<(Entity, &Aabb, &GlobalTransform)>,
+    mut cameras: Query<(Entity, &mut AzElCamera, &mut Projection)>,
+) {
+    for FrameEntities { entities, camera } in ev_frame.iter() {
+        let selected = bounds
+            .iter()
+            .filter(|(entity, _, _)| entities.is_empty() || entities.contains(entity))
+            .map(|(_, aabb, transform)| (aabb, transform));
+        let Some((center, radius)) = bounding_sphere(selected) else {
+            continue;
+        };
+        for (entity, mut az_el, mut projection) in cameras.iter_mut() {
+            if camera.is_none() || *camera == Some(entity) {
+                az_el.frame(center, radius, &mut projection);
+            }
+        }
+    }
+}
+
+// bounding sphere (center, radius) of a set of world space bounding boxes
+pub fn bounding_sphere<'a>(
+    bounds: impl Iterator<Item = (&'a Aabb, &'a GlobalTransform)>,
+) -> Option<(Vec3, f32)> {
+    let mut corners = Vec::new();
+    for (aabb, transform) in bounds {
+        let center = Vec3::from(aabb.center);
+        let half_extents = Vec3::from(aabb.half_extents);
+        for i in 0..8 {
+            let sign = Vec3::new(
+                if i & 1 == 0 { -1. } else { 1. },
+                if i & 2 == 0 { -1. } else { 1. },
+                if i & 4 == 0 { -1. } else { 1. },
+            );
+            corners.push(transform.transform_point(center + sign * half_extents));
+        }
+    }
+    if corners.is_empty() {
+        return None;
+    }
+    let min = corners
+        .iter()
+        .fold(Vec3::splat(f32::INFINITY), |a, b| a.min(*b));
+    let max = corners
+        .iter()
+        .fold(Vec3::splat(f32::NEG_INFINITY), |a, b| a.max(*b));
+    let center = (min + max) / 2.;
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0., f32::max);
+    Some((center, radius))
+}
+
+impl AzElCamera {
+    // move the focus to the center of a bounding sphere, and zoom so the sphere fills the view
+    pub fn frame(&mut self, center: Vec3, radius: f32, projection: &mut Projection) {
+        let radius = radius.max(0.01) * FRAME_MARGIN;
+        let mut view = self.view();
+        view.focus = center;
+        match projection {
+            Projection::Perspective(perspective) => {
+                // fit the sphere in the narrower of the vertical and horizontal field of view
+                let half_fov_y = perspective.fov / 2.;
+                let half_fov_x = (half_fov_y.tan() * perspective.aspect_ratio).atan();
+                view.radius = radius / half_fov_y.min(half_fov_x).sin();
+            }
+            Projection::Orthographic(orthographic) => {
+                let (width, height) = (orthographic.area.width(), orthographic.area.height());
+                if width > 0. && height > 0. {
+                    let fit = (2. * radius / height).max(2. * radius / width);
+                    orthographic.scale *= fit;
+                }
+                // keep the camera outside the framed entities
+                view.radius = view.radius.max(2. * radius);
+            }
+        }
+        self.animate_to(view, VIEW_TRANSITION);
+    }
+}
+
 fn az_el_rotation(az: f32, el: f32, up_direction: &UpDirection) -> Quat {
     match up_direction {
         UpDirection::X => {
@@ -138,12 +995,6 @@ fn az_el_translation(focus: Vec3, rotation: Quat, radius: f32) -> Vec3 {
     focus + rotation * Vec3::new(0.0, 0.0, radius)
 }
 
-fn get_primary_window_size(windows: &Query<&mut Window>) -> Vec2 {
-    let window = windows.get_single().unwrap();
-    let window = Vec2::new(window.width() as f32, window.height() as f32);
-    window
-}
-
 // /// Spawn a camera like this
 // pub fn spawn_camera(mut commands: Commands) {
 //     let translation = Vec3::new(20.0, -40., 0.);
@@ -178,18 +1029,24 @@ pub fn camera_builder(
             ..default()
         };
 
+        let mut az_el = AzElCamera {
+            radius,
+            focus,
+            up_direction: up_direction.clone(),
+            azimuth: az,
+            elevation: el,
+            ..default()
+        };
+        // the initial view, followed by the standard views
+        az_el.presets = vec![("initial".to_string(), az_el.view())];
+        az_el.presets.extend(az_el.standard_views());
+
         commands
             .spawn(Camera3dBundle {
                 transform,
                 ..Default::default()
             })
-            .insert(AzElCamera {
-                radius,
-                focus,
-                up_direction: up_direction.clone(),
-                azimuth: az,
-                elevation: el,
-            });
+            .insert(az_el);
     };
     spawn_camera
 }
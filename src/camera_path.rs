@@ -0,0 +1,162 @@
+use std::{cell::RefCell, rc::Rc};
+
+use bevy::prelude::*;
+
+use crate::{
+    camera_az_el::{AzElCamera, CameraView, Easing},
+    integrator::IntegratorStats,
+    playback::Playback,
+    recorder::{RecordedData, Recorder},
+};
+
+// Camera trajectories for reproducible videos. The camera view can be recorded with the other
+// channels, and keyframed camera paths can be played back in sync with the simulation time of the
+// integrator (the time base of the recorder), the app time, or the playback time when replaying
+// recorded data.
+
+const CAMERA_CHANNELS: [&str; 6] = [
+    "camera_focus_x",
+    "camera_focus_y",
+    "camera_focus_z",
+    "camera_azimuth",
+    "camera_elevation",
+    "camera_radius",
+];
+
+// record the view of the first AzElCamera, call before the recorder is initialized
+pub fn add_camera_channels(recorder: &mut Recorder) {
+    // the first channel reads the view once per row and the others reuse it, with one query
+    let shared = Rc::new(RefCell::new((
+        None::<QueryState<&'static AzElCamera>>,
+        [f32::NAN; 6],
+    )));
+    for (index, name) in CAMERA_CHANNELS.iter().enumerate() {
+        let shared = shared.clone();
+        recorder.add_world_channel(name, move |world: &mut World| {
+            let (query, values) = &mut *shared.borrow_mut();
+            if index == 0 {
+                let query = query.get_or_insert_with(|| world.query());
+                *values = match query.iter(world).next() {
+                    Some(az_el) => {
+                        let view = az_el.view();
+                        [
+                            view.focus.x,
+                            view.focus.y,
+                            view.focus.z,
+                            view.azimuth,
+                            view.elevation,
+                            view.radius,
+                        ]
+                    }
+                    None => [f32::NAN; 6],
+                };
+            }
+            values[index]
+        });
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum CameraPathClock {
+    Simulation, // simulation time of the integrator, as recorded by the recorder
+    Elapsed,    // app time
+    Playback,   // playback time of recorded data
+}
+
+#[derive(Component, Clone)]
+pub struct CameraPath {
+    pub keyframes: Vec<(f32, CameraView)>, // (time, view), sorted by time
+    pub easing: Easing,                    // easing between keyframes
+    pub clock: CameraPathClock,
+}
+
+impl CameraPath {
+    pub fn new(clock: CameraPathClock) -> Self {
+        CameraPath {
+            keyframes: Vec::new(),
+            easing: Easing::EaseInOut,
+            clock,
+        }
+    }
+
+    pub fn with_keyframe(mut self, time: f32, view: CameraView) -> Self {
+        let index = self.keyframes.partition_point(|(t, _)| *t <= time);
+        self.keyframes.insert(index, (time, view));
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    // a path through every recorded camera view, to replay the camera of a recorded run
+    pub fn from_recorded(recorded_data: &RecordedData) -> Option<Self> {
+        let times = recorded_data.data.get("time")?;
+        let channels = CAMERA_CHANNELS
+            .iter()
+            .map(|name| recorded_data.data.get(*name))
+            .collect::<Option<Vec<_>>>()?;
+        let mut path = CameraPath::new(CameraPathClock::Playback).with_easing(Easing::Linear);
+        for (row, time) in times.iter().enumerate() {
+            let value = |channel: usize| channels[channel][row];
+            if value(5).is_nan() {
+                continue;
+            }
+            path.keyframes.push((
+                *time,
+                CameraView {
+                    focus: Vec3::new(value(0), value(1), value(2)),
+                    azimuth: value(3),
+                    elevation: value(4),
+                    radius: value(5),
+                },
+            ));
+        }
+        Some(path)
+    }
+
+    // the view at time t, holding the first and last keyframes outside the path
+    pub fn view_at(&self, t: f32) -> Option<CameraView> {
+        let index = self.keyframes.partition_point(|(time, _)| *time <= t);
+        if index == 0 {
+            return self.keyframes.first().map(|(_, view)| *view);
+        }
+        if index >= self.keyframes.len() {
+            return self.keyframes.last().map(|(_, view)| *view);
+        }
+        let (t0, view0) = self.keyframes[index - 1];
+        let (t1, view1) = self.keyframes[index];
+        let s = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1. };
+        Some(view0.lerp(&view1, self.easing.ease(s)))
+    }
+}
+
+pub fn camera_path_system(
+    time: Res<Time>,
+    stats: Option<Res<IntegratorStats>>,
+    playback: Option<Res<Playback>>,
+    mut query: Query<(&mut AzElCamera, &mut Transform, &CameraPath)>,
+) {
+    for (mut az_el, mut transform, path) in query.iter_mut() {
+        let t = match path.clock {
+            CameraPathClock::Simulation => match &stats {
+                Some(stats) => stats.time(),
+                None => continue,
+            },
+            CameraPathClock::Elapsed => time.elapsed_seconds(),
+            CameraPathClock::Playback => match &playback {
+                Some(playback) => playback.time,
+                None => continue,
+            },
+        };
+        let Some(view) = path.view_at(t) else {
+            continue;
+        };
+        // the path takes over from user input and animations
+        az_el.target = None;
+        az_el.animation = None;
+        az_el.set_view(&view);
+        *transform = az_el.transform();
+    }
+}
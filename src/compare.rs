@@ -0,0 +1,205 @@
+use std::fmt;
+
+use crate::recorder::RecordedData;
+
+// Compare two recorded runs, e.g. the same model integrated with a different solver or step size.
+// The runs are aligned on the time rows of the reference run (the other run is interpolated), and
+// only the time range covered by both runs is compared. A comparison only passes if the runs
+// overlap, have the same channels, and every error is finite and within the tolerance.
+
+#[derive(Debug, Clone)]
+pub struct ChannelComparison {
+    pub name: String,
+    pub max_error: f32,
+    pub rms_error: f32,
+    pub max_error_time: f32,
+    pub first_exceedance: Option<f32>, // first time the error exceeds the tolerance or isn't finite
+}
+
+#[derive(Debug, Clone)]
+pub struct RunComparison {
+    pub tolerance: f32,
+    pub samples: usize,
+    pub channels: Vec<ChannelComparison>,
+    pub missing_channels: Vec<String>, // channels recorded in only one of the runs
+}
+
+pub fn compare_runs(
+    reference: &RecordedData,
+    other: &RecordedData,
+    tolerance: f32,
+) -> RunComparison {
+    // compare over the time range covered by both runs
+    let mut times = Vec::<f32>::new();
+    if let (Some(times_ref), Some((start, end))) = (reference.data.get("time"), other.time_range())
+    {
+        times = times_ref
+            .iter()
+            .copied()
+            .filter(|t| *t >= start && *t <= end)
+            .collect();
+    }
+
+    // channels recorded in both runs, sorted so the report is stable
+    let mut names: Vec<&String> = reference
+        .data
+        .keys()
+        .filter(|name| name.as_str() != "time" && other.data.contains_key(*name))
+        .collect();
+    names.sort();
+
+    let mut missing_channels: Vec<String> = reference
+        .data
+        .keys()
+        .filter(|name| !other.data.contains_key(*name))
+        .chain(
+            other
+                .data
+                .keys()
+                .filter(|name| !reference.data.contains_key(*name)),
+        )
+        .filter(|name| name.as_str() != "time")
+        .cloned()
+        .collect();
+    missing_channels.sort();
+
+    let mut channels = Vec::new();
+    for name in names {
+        let mut comparison = ChannelComparison {
+            name: name.clone(),
+            max_error: 0.,
+            rms_error: 0.,
+            max_error_time: 0.,
+            first_exceedance: None,
+        };
+        let mut sum_squared = 0.;
+        for t in times.iter() {
+            let (Some(value_ref), Some(value)) =
+                (reference.value_at(name, *t), other.value_at(name, *t))
+            else {
+                continue;
+            };
+            // a diverged run gives NaN or infinite errors, which never compare as large
+            let error = (value - value_ref).abs();
+            sum_squared += error * error;
+            if comparison.max_error.is_finite()
+                && (!error.is_finite() || error > comparison.max_error)
+            {
+                comparison.max_error = error;
+                comparison.max_error_time = *t;
+            }
+            if (!error.is_finite() || error > tolerance) && comparison.first_exceedance.is_none() {
+                comparison.first_exceedance = Some(*t);
+            }
+        }
+        if !times.is_empty() {
+            comparison.rms_error = (sum_squared / times.len() as f32).sqrt();
+        }
+        channels.push(comparison);
+    }
+
+    RunComparison {
+        tolerance,
+        samples: times.len(),
+        channels,
+        missing_channels,
+    }
+}
+
+impl RunComparison {
+    // false if nothing was compared or the runs recorded different channels
+    pub fn within_tolerance(&self) -> bool {
+        self.samples > 0
+            && self.missing_channels.is_empty()
+            && self.channels.iter().all(|c| c.first_exceedance.is_none())
+    }
+
+    pub fn write_report(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for RunComparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "compared {} samples, tolerance {:e}",
+            self.samples, self.tolerance
+        )?;
+        writeln!(
+            f,
+            "{:<24} {:>12} {:>12} {:>12} {:>12}",
+            "channel", "max error", "at time", "rms error", "exceeds at"
+        )?;
+        for c in self.channels.iter() {
+            let exceeds = match c.first_exceedance {
+                Some(t) => format!("{:.4}", t),
+                None => "-".to_string(),
+            };
+            writeln!(
+                f,
+                "{:<24} {:>12.4e} {:>12.4} {:>12.4e} {:>12}",
+                c.name, c.max_error, c.max_error_time, c.rms_error, exceeds
+            )?;
+        }
+        if self.samples == 0 {
+            writeln!(f, "the runs don't overlap in time")?;
+        }
+        for name in self.missing_channels.iter() {
+            writeln!(f, "{:<24} recorded in only one run", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn run(columns: &[(&str, Vec<f32>)]) -> RecordedData {
+        RecordedData {
+            data: columns
+                .iter()
+                .map(|(name, values)| (name.to_string(), values.clone()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn identical_runs_are_within_tolerance() {
+        let reference = run(&[("time", vec![0., 1., 2.]), ("x", vec![0., 1., 2.])]);
+        assert!(compare_runs(&reference, &reference, 1e-3).within_tolerance());
+    }
+
+    #[test]
+    fn diverged_run_exceeds_the_tolerance() {
+        let reference = run(&[("time", vec![0., 1., 2.]), ("x", vec![0., 1., 2.])]);
+        let other = run(&[
+            ("time", vec![0., 1., 2.]),
+            ("x", vec![0., f32::NAN, f32::NAN]),
+        ]);
+        let comparison = compare_runs(&reference, &other, 1e-3);
+        assert!(comparison.channels[0].first_exceedance.is_some());
+        assert!(!comparison.within_tolerance());
+    }
+
+    #[test]
+    fn runs_without_overlap_or_with_other_channels_fail() {
+        let reference = run(&[("time", vec![0., 1.]), ("x", vec![0., 1.])]);
+        let later = run(&[("time", vec![2., 3.]), ("x", vec![0., 1.])]);
+        let comparison = compare_runs(&reference, &later, 1e-3);
+        assert_eq!(comparison.samples, 0);
+        assert!(!comparison.within_tolerance());
+
+        let other = run(&[
+            ("time", vec![0., 1.]),
+            ("x", vec![0., 1.]),
+            ("y", vec![0., 1.]),
+        ]);
+        let comparison = compare_runs(&reference, &other, 1e-3);
+        assert_eq!(comparison.missing_channels, vec!["y".to_string()]);
+        assert!(!comparison.within_tolerance());
+    }
+}
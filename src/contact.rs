@@ -0,0 +1,106 @@
+use bevy::prelude::*;
+
+use crate::{
+    environment::Ground,
+    integrator::{PhysicsInputs, Stateful},
+};
+
+// Penalty based contact between bodies and the environment's Ground. Each contact point below the
+// ground is pushed out by a spring-damper along the ground normal, and slowed along the ground by
+// Coulomb friction. The friction is regularized below `slip_velocity` so resting bodies don't chatter.
+// Add ground_contact::<T> to the evaluate systems of the physics schedule (PhysicsSet::Evaluate),
+// and contact_inputs_changed to the app (CoreSet::Last) so the integrator restarts from a fresh
+// derivative when the ground or the contact parameters change.
+
+pub trait GroundContact {
+    // world position and velocity of each point that can touch the ground
+    fn contact_points(&self) -> Vec<(Vec3, Vec3)>;
+    fn add_contact_force(&mut self, point: usize, force: Vec3);
+}
+
+#[derive(Resource)]
+pub struct ContactParameters {
+    pub stiffness: f32,     // N/m
+    pub damping: f32,       // N/(m/s)
+    pub friction: f32,      // Coulomb friction coefficient
+    pub slip_velocity: f32, // m/s, full friction is reached at this sliding speed
+}
+
+impl Default for ContactParameters {
+    fn default() -> Self {
+        ContactParameters {
+            stiffness: 1e4,
+            damping: 200.,
+            friction: 0.8,
+            slip_velocity: 0.01,
+        }
+    }
+}
+
+// force on a contact point from the ground, zero when the point is above the ground
+pub fn contact_force(
+    ground: &Ground,
+    parameters: &ContactParameters,
+    position: Vec3,
+    velocity: Vec3,
+) -> Vec3 {
+    let up = ground.up();
+    let depth = ground.height(position) - position.dot(up);
+    if depth <= 0. {
+        return Vec3::ZERO;
+    }
+
+    // penetration measured along the normal of a sloped ground
+    let normal = ground.normal(position);
+    let normal_depth = depth * normal.dot(up);
+    let normal_velocity = velocity.dot(normal);
+    // the ground can push but not pull
+    let normal_force =
+        (parameters.stiffness * normal_depth - parameters.damping * normal_velocity).max(0.);
+
+    let sliding_velocity = velocity - normal_velocity * normal;
+    let friction_force = -parameters.friction * normal_force * sliding_velocity
+        / sliding_velocity.length().max(parameters.slip_velocity);
+
+    normal_force * normal + friction_force
+}
+
+pub fn ground_contact<T: Component + Stateful + GroundContact>(
+    ground: Option<Res<Ground>>,
+    parameters: Option<Res<ContactParameters>>,
+    mut query: Query<&mut T>,
+) {
+    let Some(ground) = ground else {
+        return;
+    };
+    let default_parameters = ContactParameters::default();
+    let parameters = parameters.as_deref().unwrap_or(&default_parameters);
+
+    for mut body in query.iter_mut() {
+        for (point, (position, velocity)) in body.contact_points().into_iter().enumerate() {
+            let force = contact_force(&ground, parameters, position, velocity);
+            if force != Vec3::ZERO {
+                body.add_contact_force(point, force);
+            }
+        }
+    }
+}
+
+// marks PhysicsInputs as changed when the Ground or the ContactParameters are inserted, changed or
+// removed
+pub fn contact_inputs_changed(
+    mut commands: Commands,
+    ground: Option<Res<Ground>>,
+    parameters: Option<Res<ContactParameters>>,
+    mut present: Local<(bool, bool)>,
+) {
+    let changed = ground.as_ref().is_some_and(|ground| ground.is_changed())
+        || parameters
+            .as_ref()
+            .is_some_and(|parameters| parameters.is_changed())
+        || *present != (ground.is_some(), parameters.is_some());
+    *present = (ground.is_some(), parameters.is_some());
+    if changed {
+        commands.insert_resource(PhysicsInputs);
+    }
+}
@@ -0,0 +1,182 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    pbr::NotShadowCaster, prelude::*, render::view::NoFrustumCulling, transform::TransformSystem,
+};
+
+use crate::{
+    environment::{line_mesh, unlit},
+    integrator::Stateful,
+};
+
+// Debug arrows for vectors acting on stateful bodies, e.g. the force, velocity and acceleration.
+// Each arrow starts at the body's transform, and is `scale` long per unit of the vector.
+// All arrows of a kind share one line mesh, which is rebuilt every frame, and are toggled with `key`.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VectorKind {
+    Force,
+    Velocity,
+    Acceleration,
+}
+
+pub trait DebugVectors {
+    // world space vector of the given kind, None if the body doesn't have one
+    fn debug_vector(&self, kind: VectorKind) -> Option<Vec3>;
+}
+
+#[derive(Clone)]
+pub struct VectorStyle {
+    pub kind: VectorKind,
+    pub scale: f32, // arrow length in meters per unit of the vector
+    pub color: Color,
+    pub key: KeyCode,
+    pub visible: bool,
+}
+
+pub struct DebugVectorsPlugin<T> {
+    pub vectors: Vec<VectorStyle>,
+    pub marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for DebugVectorsPlugin<T> {
+    fn default() -> Self {
+        DebugVectorsPlugin {
+            vectors: vec![
+                VectorStyle {
+                    kind: VectorKind::Force,
+                    scale: 0.1,
+                    color: Color::rgb(0.9, 0.2, 0.2),
+                    key: KeyCode::F1,
+                    visible: false,
+                },
+                VectorStyle {
+                    kind: VectorKind::Velocity,
+                    scale: 0.5,
+                    color: Color::rgb(0.2, 0.6, 0.9),
+                    key: KeyCode::F2,
+                    visible: false,
+                },
+                VectorStyle {
+                    kind: VectorKind::Acceleration,
+                    scale: 0.1,
+                    color: Color::rgb(0.2, 0.8, 0.3),
+                    key: KeyCode::F3,
+                    visible: false,
+                },
+            ],
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Component + Stateful + DebugVectors> Plugin for DebugVectorsPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DebugDraw {
+            vectors: self.vectors.clone(),
+        })
+        .add_startup_system(spawn_debug_arrows)
+        .add_system(toggle_debug_vectors)
+        .add_system(
+            draw_debug_vectors::<T>
+                .in_base_set(CoreSet::PostUpdate)
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct DebugDraw {
+    pub vectors: Vec<VectorStyle>,
+}
+
+// line mesh with the arrows of one entry of DebugDraw::vectors
+#[derive(Component)]
+pub struct DebugArrows(pub usize);
+
+const HEAD_LENGTH: f32 = 0.2; // fraction of the arrow length
+const HEAD_WIDTH: f32 = 0.08; // fraction of the arrow length
+
+fn spawn_debug_arrows(
+    mut commands: Commands,
+    debug_draw: Res<DebugDraw>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (index, style) in debug_draw.vectors.iter().enumerate() {
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(line_mesh(&[(Vec3::ZERO, Vec3::ZERO)])), // replaced when drawn
+                material: materials.add(unlit(style.color)),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            DebugArrows(index),
+            NotShadowCaster,
+            NoFrustumCulling, // the arrows move every frame, so the bounds are never up to date
+        ));
+    }
+}
+
+fn toggle_debug_vectors(keyboard_input: Res<Input<KeyCode>>, mut debug_draw: ResMut<DebugDraw>) {
+    for style in debug_draw.vectors.iter_mut() {
+        if keyboard_input.just_pressed(style.key) {
+            style.visible = !style.visible;
+        }
+    }
+}
+
+// shaft and a four line head
+pub fn arrow_segments(start: Vec3, vector: Vec3) -> Vec<(Vec3, Vec3)> {
+    let length = vector.length();
+    if length < f32::EPSILON {
+        return Vec::new();
+    }
+    let end = start + vector;
+    let direction = vector / length;
+    let base = end - direction * length * HEAD_LENGTH;
+    let side = direction.any_orthonormal_vector() * length * HEAD_WIDTH;
+    let other_side = direction.cross(side);
+    vec![
+        (start, end),
+        (end, base + side),
+        (end, base - side),
+        (end, base + other_side),
+        (end, base - other_side),
+    ]
+}
+
+pub fn draw_debug_vectors<T: Component + DebugVectors>(
+    debug_draw: Res<DebugDraw>,
+    bodies: Query<(&T, &GlobalTransform)>,
+    mut arrows: Query<(&DebugArrows, &Handle<Mesh>, &mut Visibility)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (arrows, mesh, mut visibility) in arrows.iter_mut() {
+        let Some(style) = debug_draw.vectors.get(arrows.0) else {
+            continue;
+        };
+        let segments: Vec<(Vec3, Vec3)> = if style.visible {
+            bodies
+                .iter()
+                .filter_map(|(body, transform)| {
+                    body.debug_vector(style.kind)
+                        .map(|vector| arrow_segments(transform.translation(), vector * style.scale))
+                })
+                .flatten()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // hide the mesh rather than uploading an empty one
+        if segments.is_empty() {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            *mesh = line_mesh(&segments);
+        }
+    }
+}
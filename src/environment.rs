@@ -1,49 +1,760 @@
 use std::f32::consts::PI;
 
-use bevy::prelude::*;
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        mesh::Indices,
+        render_resource::{PrimitiveTopology, TextureFormat},
+    },
+};
+
+use crate::camera_az_el::UpDirection;
+
+// Describes the static scene around a model: the ground, scale references and lighting.
+// The ground passes through the origin, perpendicular to `up_direction`, so it should match the
+// up direction given to the camera.
+
+pub struct EnvironmentConfig {
+    pub up_direction: UpDirection,
+    pub ground_size: f32, // side length of the square ground plane in meters, 0 for no ground
+    pub ground_color: Color,
+    pub grid: Option<Grid>,
+    pub axis_length: Option<f32>, // world X (red), Y (green) and Z (blue) axes drawn at the origin
+    pub lighting: LightingPreset,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        EnvironmentConfig {
+            up_direction: UpDirection::Z,
+            ground_size: 1000.,
+            ground_color: Color::WHITE,
+            grid: Some(Grid::default()),
+            axis_length: Some(1.),
+            lighting: LightingPreset::Default,
+        }
+    }
+}
+
+// Lines on the ground every `minor_spacing`, with every line at a multiple of `major_spacing`
+// drawn in `major_color`. The grid covers a square of side `size` centered on the origin.
+pub struct Grid {
+    pub size: f32,
+    pub major_spacing: f32,
+    pub minor_spacing: f32,
+    pub major_color: Color,
+    pub minor_color: Color,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid {
+            size: 20.,
+            major_spacing: 1.,
+            minor_spacing: 0.1,
+            major_color: Color::rgb(0.3, 0.3, 0.3),
+            minor_color: Color::rgb(0.7, 0.7, 0.7),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightingPreset {
+    Default, // a single point light above the origin with soft ambient light
+    Studio,  // key and fill lights, no hard shadows
+    Outdoor, // bright directional sun with shadows
+    None,    // only the ambient light already in the world
+}
+
+// rotation from bevy's Y up to the configured up direction
+pub fn up_rotation(up_direction: &UpDirection) -> Quat {
+    match up_direction {
+        UpDirection::X => Quat::from_rotation_z(-PI / 2.),
+        UpDirection::Y => Quat::IDENTITY,
+        UpDirection::Z => Quat::from_rotation_x(PI / 2.),
+    }
+}
 
 pub fn build_environment(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
+    config: &EnvironmentConfig,
 ) {
-    // add ambient light
-    commands.insert_resource(AmbientLight {
-        color: Color::WHITE,
-        brightness: 0.2,
-    });
+    // the light positions below are written with Z up
+    let frame = up_rotation(&config.up_direction) * up_rotation(&UpDirection::Z).inverse();
+    build_lighting(commands, config.lighting, frame);
 
-    // add point light
-    commands.spawn(PointLightBundle {
-        transform: Transform::from_xyz(1.0, -5.0, 3.0),
-        point_light: PointLight {
-            intensity: 1600.0, // lumens - roughly a 100W non-halogen incandescent bulb
-            color: Color::WHITE,
-            shadow_depth_bias: 0.1,
-            shadow_normal_bias: 0.9,
-            shadows_enabled: true,
+    // add ground plane
+    if config.ground_size > 0. {
+        commands.insert_resource(Ground::Plane {
+            up: config.up_direction.vector(),
+        });
+        commands.spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Plane {
+                size: config.ground_size,
+                subdivisions: 10,
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: config.ground_color,
+                perceptual_roughness: 1.0,
+                ..default()
+            }),
+            transform: Transform::from_rotation(up_rotation(&config.up_direction)),
             ..default()
-        },
-        ..default()
+        });
+    }
+
+    // add grid lines, slightly above the ground to avoid z-fighting
+    let grid_lines = config.grid.as_ref().and_then(|grid| {
+        let lines = grid.lines();
+        if lines.is_none() {
+            eprintln!(
+                "Skipping the grid, its size {} and minor spacing {} must be positive and give at most {} lines",
+                grid.size, grid.minor_spacing, MAX_GRID_LINES
+            );
+        }
+        lines.map(|lines| (grid, lines))
     });
+    if let Some((grid, (major, minor))) = grid_lines {
+        let transform = Transform::from_rotation(up_rotation(&config.up_direction))
+            * Transform::from_xyz(0., 0.001, 0.);
+        for (lines, color) in [(minor, grid.minor_color), (major, grid.major_color)] {
+            commands.spawn((
+                PbrBundle {
+                    mesh: meshes.add(line_mesh(&lines)),
+                    material: materials.add(unlit(color)),
+                    transform,
+                    ..default()
+                },
+                NotShadowCaster,
+            ));
+        }
+    }
 
-    // add ground plane
-    let ground_transform = Transform::from_translation(Vec3 {
-        x: 0.,
-        y: 0.,
-        z: 0.,
-    }) * Transform::from_rotation(Quat::from_axis_angle(Vec3::X, PI / 2.));
+    // add world axis triad
+    if let Some(length) = config.axis_length {
+        let axes = [
+            (Vec3::X, Color::RED),
+            (Vec3::Y, Color::GREEN),
+            (Vec3::Z, Color::BLUE),
+        ];
+        for (axis, color) in axes {
+            commands.spawn((
+                PbrBundle {
+                    mesh: meshes.add(line_mesh(&[(Vec3::ZERO, axis * length)])),
+                    material: materials.add(unlit(color)),
+                    ..default()
+                },
+                NotShadowCaster,
+            ));
+        }
+    }
+}
+
+fn build_lighting(commands: &mut Commands, lighting: LightingPreset, frame: Quat) {
+    match lighting {
+        LightingPreset::Default => {
+            commands.insert_resource(AmbientLight {
+                color: Color::WHITE,
+                brightness: 0.2,
+            });
+            commands.spawn(PointLightBundle {
+                transform: Transform::from_translation(frame * Vec3::new(1.0, -5.0, 3.0)),
+                point_light: PointLight {
+                    intensity: 1600.0, // lumens - roughly a 100W non-halogen incandescent bulb
+                    color: Color::WHITE,
+                    shadow_depth_bias: 0.1,
+                    shadow_normal_bias: 0.9,
+                    shadows_enabled: true,
+                    ..default()
+                },
+                ..default()
+            });
+        }
+        LightingPreset::Studio => {
+            commands.insert_resource(AmbientLight {
+                color: Color::WHITE,
+                brightness: 0.4,
+            });
+            for (position, intensity) in [
+                (Vec3::new(4.0, -4.0, 5.0), 2400.0), // key
+                (Vec3::new(-5.0, -2.0, 3.0), 800.0), // fill
+                (Vec3::new(0.0, 5.0, 4.0), 600.0),   // back
+            ] {
+                commands.spawn(PointLightBundle {
+                    transform: Transform::from_translation(frame * position),
+                    point_light: PointLight {
+                        intensity,
+                        range: 50.0,
+                        ..default()
+                    },
+                    ..default()
+                });
+            }
+        }
+        LightingPreset::Outdoor => {
+            commands.insert_resource(AmbientLight {
+                color: Color::rgb(0.8, 0.9, 1.0),
+                brightness: 0.3,
+            });
+            commands.spawn(DirectionalLightBundle {
+                transform: Transform::from_translation(frame * Vec3::new(2.0, -3.0, 5.0))
+                    .looking_at(Vec3::ZERO, frame * Vec3::Z),
+                directional_light: DirectionalLight {
+                    illuminance: 20000.0, // lux - overcast daylight
+                    shadows_enabled: true,
+                    ..default()
+                },
+                ..default()
+            });
+        }
+        LightingPreset::None => {}
+    }
+}
+
+impl Grid {
+    // line segments in the XZ plane (bevy's ground plane), split into major and minor lines. None
+    // unless the size and spacing are positive and give at most MAX_GRID_LINES lines per direction
+    fn lines(&self) -> Option<(LineSegments, LineSegments)> {
+        let mut major = Vec::new();
+        let mut minor = Vec::new();
+        let half_size = self.size / 2.;
+        let count = (half_size / self.minor_spacing).floor();
+        if !(self.size > 0. && self.minor_spacing > 0. && count <= MAX_GRID_LINES as f32 / 2.) {
+            return None;
+        }
+        let count = count as i32;
+        let major_every = (self.major_spacing / self.minor_spacing).round().max(1.) as i32;
+        for i in -count..=count {
+            let offset = i as f32 * self.minor_spacing;
+            let lines = if i % major_every == 0 {
+                &mut major
+            } else {
+                &mut minor
+            };
+            lines.push((
+                Vec3::new(offset, 0., -half_size),
+                Vec3::new(offset, 0., half_size),
+            ));
+            lines.push((
+                Vec3::new(-half_size, 0., offset),
+                Vec3::new(half_size, 0., offset),
+            ));
+        }
+        Some((major, minor))
+    }
+}
+
+// more lines than pixels across the screen would only draw a solid plane
+const MAX_GRID_LINES: i32 = 10_000;
+
+type LineSegments = Vec<(Vec3, Vec3)>;
+
+// mesh drawn as separate line segments
+pub fn line_mesh(segments: &[(Vec3, Vec3)]) -> Mesh {
+    let positions: Vec<[f32; 3]> = segments
+        .iter()
+        .flat_map(|(start, end)| [start.to_array(), end.to_array()])
+        .collect();
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh
+}
+
+pub fn unlit(color: Color) -> StandardMaterial {
+    StandardMaterial {
+        base_color: color,
+        unlit: true,
+        ..default()
+    }
+}
+
+// Ground definition shared by rendering and physics, e.g. for ground contact forces.
+// Inserted by build_environment (an infinite plane through the origin) and build_terrain.
+#[derive(Resource, Clone)]
+pub enum Ground {
+    Plane { up: Vec3 },
+    Terrain(Terrain),
+}
+
+impl Ground {
+    pub fn up(&self) -> Vec3 {
+        match self {
+            Ground::Plane { up } => *up,
+            Ground::Terrain(terrain) => terrain.up_direction.vector(),
+        }
+    }
+
+    // height of the ground along the up direction, below or above a world position
+    pub fn height(&self, position: Vec3) -> f32 {
+        match self {
+            Ground::Plane { .. } => 0.,
+            Ground::Terrain(terrain) => terrain.height(position),
+        }
+    }
+
+    pub fn normal(&self, position: Vec3) -> Vec3 {
+        match self {
+            Ground::Plane { up } => *up,
+            Ground::Terrain(terrain) => terrain.normal(position),
+        }
+    }
+}
+
+// Ground heights on a regular grid centered on the origin, used both to render non-flat ground and
+// to query the ground height and normal from physics systems.
+// The grid spans `size` along the two ground axes (X and Z of bevy's Y up frame, rotated to
+// `up_direction` like the ground plane), with heights along the up direction. Between grid points
+// the height is interpolated bilinearly, and outside the grid the edge heights are extended.
+#[derive(Clone)]
+pub struct Terrain {
+    pub size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    pub heights: Vec<f32>, // row major, `columns` heights per row
+    pub up_direction: UpDirection,
+}
+
+// ISO 8608 road roughness classes, from very good (A) to very poor (H)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoadClass {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+}
+
+impl RoadClass {
+    // displacement power spectral density at the reference spatial frequency of 0.1 cycles/m, in m^3
+    pub fn reference_psd(&self) -> f32 {
+        let class = *self as i32;
+        16e-6 * 4_f32.powi(class)
+    }
+}
+
+// largest grid built from a function, 64 MB of heights
+const MAX_TERRAIN_HEIGHTS: f32 = (1 << 24) as f32;
+
+impl Terrain {
+    // None unless the heights fill a grid of at least 2x2 heights
+    pub fn from_heights(
+        size: Vec2,
+        columns: usize,
+        heights: Vec<f32>,
+        up_direction: UpDirection,
+    ) -> Option<Self> {
+        let rows = heights.len() / columns.max(1);
+        if columns < 2 || rows < 2 || rows * columns != heights.len() {
+            return None;
+        }
+        Some(Terrain {
+            size,
+            columns,
+            rows,
+            heights,
+            up_direction,
+        })
+    }
+
+    // heights from a function of the ground coordinates, sampled every `spacing` meters. None if the
+    // spacing isn't positive or the grid would have more than MAX_TERRAIN_HEIGHTS heights
+    pub fn from_fn(
+        size: Vec2,
+        spacing: f32,
+        up_direction: UpDirection,
+        height: impl Fn(f32, f32) -> f32,
+    ) -> Option<Self> {
+        let columns = (size.x / spacing).round().max(1.) + 1.;
+        let rows = (size.y / spacing).round().max(1.) + 1.;
+        if !(spacing > 0. && size.is_finite() && columns * rows <= MAX_TERRAIN_HEIGHTS) {
+            return None;
+        }
+        let (columns, rows) = (columns as usize, rows as usize);
+        let mut heights = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = (column as f32 / (columns - 1) as f32 - 0.5) * size.x;
+                let z = (row as f32 / (rows - 1) as f32 - 0.5) * size.y;
+                heights.push(height(x, z));
+            }
+        }
+        Terrain::from_heights(size, columns, heights, up_direction)
+    }
+
+    // egg crate of sine bumps, `amplitude` high with `wavelength` between bumps, see from_fn
+    pub fn sine_bumps(
+        size: Vec2,
+        spacing: f32,
+        amplitude: f32,
+        wavelength: f32,
+        up_direction: UpDirection,
+    ) -> Option<Self> {
+        let k = 2. * PI / wavelength;
+        Terrain::from_fn(size, spacing, up_direction, |x, z| {
+            amplitude * (k * x).sin() * (k * z).sin()
+        })
+    }
+
+    // random roughness following the ISO 8608 displacement spectrum Gd(n) = Gd(n0) (n / n0)^-2,
+    // synthesized as a sum of sine waves with random phases and directions, see from_fn
+    pub fn iso_8608(
+        size: Vec2,
+        spacing: f32,
+        class: RoadClass,
+        seed: u64,
+        up_direction: UpDirection,
+    ) -> Option<Self> {
+        const REFERENCE_FREQUENCY: f32 = 0.1; // cycles/m
+        const WAVES: usize = 200;
+        // the standard covers 0.011 to 2.83 cycles/m, limited by what the grid can represent
+        let min_frequency = 0.011;
+        let max_frequency = 2.83_f32.min(0.5 / spacing);
+        let frequency_step = (max_frequency - min_frequency) / WAVES as f32;
+
+        let mut random = XorShift::new(seed);
+        let waves: Vec<(f32, Vec2, f32)> = (0..WAVES)
+            .map(|i| {
+                let frequency = min_frequency + (i as f32 + 0.5) * frequency_step;
+                let psd = class.reference_psd() * (frequency / REFERENCE_FREQUENCY).powi(-2);
+                let amplitude = (2. * psd * frequency_step).sqrt();
+                let direction = 2. * PI * random.next_f32();
+                let wave_vector = 2. * PI * frequency * Vec2::new(direction.cos(), direction.sin());
+                let phase = 2. * PI * random.next_f32();
+                (amplitude, wave_vector, phase)
+            })
+            .collect();
+
+        Terrain::from_fn(size, spacing, up_direction, |x, z| {
+            waves
+                .iter()
+                .map(|(amplitude, wave_vector, phase)| {
+                    amplitude * (wave_vector.dot(Vec2::new(x, z)) + phase).sin()
+                })
+                .sum()
+        })
+    }
+
+    // heights from the first channel of an image, black is 0 and white is `max_height`. Float images
+    // are scaled as is. None for pixel formats that aren't supported, images smaller than 2x2, or
+    // image data that doesn't match the size
+    pub fn from_image(
+        image: &Image,
+        size: Vec2,
+        max_height: f32,
+        up_direction: UpDirection,
+    ) -> Option<Self> {
+        let unorm8 = |pixel: &[u8]| pixel[0] as f32 / u8::MAX as f32;
+        let unorm16 =
+            |pixel: &[u8]| u16::from_le_bytes([pixel[0], pixel[1]]) as f32 / u16::MAX as f32;
+        let float32 = |pixel: &[u8]| f32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+        let (bytes_per_pixel, decode): PixelDecoder = match image.texture_descriptor.format {
+            TextureFormat::R8Unorm => (1, &unorm8),
+            TextureFormat::Rg8Unorm => (2, &unorm8),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => (4, &unorm8),
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+                (4, &|pixel: &[u8]| pixel[2] as f32 / u8::MAX as f32) // red is the third byte
+            }
+            TextureFormat::R16Uint | TextureFormat::R16Unorm => (2, &unorm16),
+            TextureFormat::Rg16Uint | TextureFormat::Rg16Unorm => (4, &unorm16),
+            TextureFormat::Rgba16Uint | TextureFormat::Rgba16Unorm => (8, &unorm16),
+            TextureFormat::R32Float => (4, &float32),
+            TextureFormat::Rg32Float => (8, &float32),
+            TextureFormat::Rgba32Float => (16, &float32),
+            _ => return None,
+        };
+        let columns = image.texture_descriptor.size.width as usize;
+        let rows = image.texture_descriptor.size.height as usize;
+        if columns < 2 || rows < 2 {
+            return None;
+        }
+        let heights = image
+            .data
+            .chunks_exact(bytes_per_pixel)
+            .map(|pixel| decode(pixel) * max_height)
+            .collect();
+        Terrain::from_heights(size, columns, heights, up_direction)
+    }
+
+    // height of the ground along the up direction, below or above a world position
+    pub fn height(&self, position: Vec3) -> f32 {
+        let local = up_rotation(&self.up_direction).inverse() * position;
+        self.sample(local.x, local.z).0
+    }
+
+    // world space normal of the ground below or above a world position
+    pub fn normal(&self, position: Vec3) -> Vec3 {
+        let local = up_rotation(&self.up_direction).inverse() * position;
+        let (_, gradient) = self.sample(local.x, local.z);
+        up_rotation(&self.up_direction) * Terrain::local_normal(gradient)
+    }
+
+    fn local_normal(gradient: Vec2) -> Vec3 {
+        Vec3::new(-gradient.x, 1., -gradient.y).normalize()
+    }
+
+    fn grid_height(&self, column: usize, row: usize) -> f32 {
+        self.heights[row * self.columns + column]
+    }
+
+    // bilinear height and its gradient at ground coordinates
+    fn sample(&self, x: f32, z: f32) -> (f32, Vec2) {
+        let cell_size = self.size / Vec2::new((self.columns - 1) as f32, (self.rows - 1) as f32);
+        let u = ((x / self.size.x + 0.5) * (self.columns - 1) as f32)
+            .clamp(0., (self.columns - 1) as f32);
+        let v =
+            ((z / self.size.y + 0.5) * (self.rows - 1) as f32).clamp(0., (self.rows - 1) as f32);
+        let column = (u.floor() as usize).min(self.columns - 2);
+        let row = (v.floor() as usize).min(self.rows - 2);
+        let (s, t) = (u - column as f32, v - row as f32);
+
+        let h00 = self.grid_height(column, row);
+        let h10 = self.grid_height(column + 1, row);
+        let h01 = self.grid_height(column, row + 1);
+        let h11 = self.grid_height(column + 1, row + 1);
+        let height =
+            h00 * (1. - s) * (1. - t) + h10 * s * (1. - t) + h01 * (1. - s) * t + h11 * s * t;
+        let gradient = Vec2::new(
+            ((h10 - h00) * (1. - t) + (h11 - h01) * t) / cell_size.x,
+            ((h01 - h00) * (1. - s) + (h11 - h10) * s) / cell_size.y,
+        );
+        (height, gradient)
+    }
+
+    // render mesh in bevy's Y up frame, rotate it with `up_rotation` to place it in the world
+    pub fn mesh(&self) -> Mesh {
+        let mut positions = Vec::with_capacity(self.heights.len());
+        let mut normals = Vec::with_capacity(self.heights.len());
+        let mut uvs = Vec::with_capacity(self.heights.len());
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let u = column as f32 / (self.columns - 1) as f32;
+                let v = row as f32 / (self.rows - 1) as f32;
+                let x = (u - 0.5) * self.size.x;
+                let z = (v - 0.5) * self.size.y;
+                let (_, gradient) = self.sample(x, z);
+                positions.push([x, self.grid_height(column, row), z]);
+                normals.push(Terrain::local_normal(gradient).to_array());
+                uvs.push([u, v]);
+            }
+        }
+
+        let mut indices = Vec::with_capacity((self.columns - 1) * (self.rows - 1) * 6);
+        for row in 0..self.rows - 1 {
+            for column in 0..self.columns - 1 {
+                let i = (row * self.columns + column) as u32;
+                let next_row = i + self.columns as u32;
+                indices.extend_from_slice(&[i, next_row, i + 1, i + 1, next_row, next_row + 1]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
+
+// spawns the terrain mesh, and inserts it as the Ground so physics systems can query it
+pub fn build_terrain(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    terrain: Terrain,
+    color: Color,
+) {
     commands.spawn(PbrBundle {
-        mesh: meshes.add(Mesh::from(shape::Plane {
-            size: 1000.0,
-            subdivisions: 10,
-        })),
+        mesh: meshes.add(terrain.mesh()),
         material: materials.add(StandardMaterial {
-            base_color: Color::WHITE,
+            base_color: color,
             perceptual_roughness: 1.0,
             ..default()
         }),
-        transform: ground_transform,
+        transform: Transform::from_rotation(up_rotation(&terrain.up_direction)),
         ..default()
     });
+    commands.insert_resource(Ground::Terrain(terrain));
+}
+
+// Heightmap image waiting to be loaded, see heightmap_terrain_loader
+#[derive(Resource)]
+pub struct Heightmap {
+    pub image: Handle<Image>,
+    pub size: Vec2,
+    pub max_height: f32,
+    pub color: Color,
+    pub up_direction: UpDirection,
+}
+
+pub fn heightmap_terrain_loader(
+    file_name: &str,
+    size: Vec2,
+    max_height: f32,
+    color: Color,
+    up_direction: UpDirection,
+) -> impl Fn(Commands, Res<AssetServer>) {
+    let file_name = file_name.to_string();
+    let load_heightmap = move |mut commands: Commands, asset_server: Res<AssetServer>| {
+        commands.insert_resource(Heightmap {
+            image: asset_server.load(file_name.as_str()),
+            size,
+            max_height,
+            color,
+            up_direction: up_direction.clone(),
+        });
+    };
+    load_heightmap
+}
+
+// builds the terrain once the heightmap image is loaded
+pub fn spawn_heightmap_terrain(
+    mut commands: Commands,
+    heightmap: Option<Res<Heightmap>>,
+    images: Res<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(heightmap) = heightmap else {
+        return;
+    };
+    let Some(image) = images.get(&heightmap.image) else {
+        return;
+    };
+    commands.remove_resource::<Heightmap>();
+    let Some(terrain) = Terrain::from_image(
+        image,
+        heightmap.size,
+        heightmap.max_height,
+        heightmap.up_direction.clone(),
+    ) else {
+        let size = image.texture_descriptor.size;
+        eprintln!(
+            "Heightmap with format {:?} and size {}x{} is not supported, it needs at least 2x2 pixels",
+            image.texture_descriptor.format, size.width, size.height
+        );
+        return;
+    };
+    build_terrain(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        terrain,
+        heightmap.color,
+    );
+}
+
+// bytes per pixel, and the value of the first channel of a pixel
+type PixelDecoder<'a> = (usize, &'a dyn Fn(&[u8]) -> f32);
+
+// xorshift generator, so the roughness is reproducible for a given seed
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // the state must not be zero, which the xor gives for one seed
+        XorShift((seed ^ 0x9e37_79b9_7f4a_7c15).max(1))
+    }
+
+    // uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    use super::*;
+
+    fn image(format: TextureFormat, data: Vec<u8>) -> Image {
+        let size = Extent3d {
+            width: 2,
+            height: 2,
+            depth_or_array_layers: 1,
+        };
+        Image::new(size, TextureDimension::D2, data, format)
+    }
+
+    #[test]
+    fn heightmap_pixels_are_decoded_by_format() {
+        let heights = [0., 0.25, 0.5, 1.];
+        let size = Vec2::splat(1.);
+
+        let data = heights.iter().flat_map(|h: &f32| h.to_le_bytes()).collect();
+        let terrain = Terrain::from_image(
+            &image(TextureFormat::R32Float, data),
+            size,
+            2.,
+            UpDirection::Y,
+        );
+        assert_eq!(terrain.unwrap().heights, vec![0., 0.5, 1., 2.]);
+
+        let data = heights
+            .iter()
+            .flat_map(|h| ((h * u16::MAX as f32).round() as u16).to_le_bytes())
+            .collect();
+        let terrain = Terrain::from_image(
+            &image(TextureFormat::R16Uint, data),
+            size,
+            2.,
+            UpDirection::Y,
+        );
+        for (height, expected) in terrain.unwrap().heights.iter().zip([0., 0.5, 1., 2.]) {
+            assert!((height - expected).abs() < 1e-4);
+        }
+
+        let data = vec![0; 8];
+        let terrain = Terrain::from_image(
+            &image(TextureFormat::R16Float, data),
+            size,
+            2.,
+            UpDirection::Y,
+        );
+        assert!(terrain.is_none());
+
+        let column = Extent3d {
+            width: 1,
+            height: 2,
+            depth_or_array_layers: 1,
+        };
+        let image = Image::new(
+            column,
+            TextureDimension::D2,
+            vec![0; 2],
+            TextureFormat::R8Unorm,
+        );
+        assert!(Terrain::from_image(&image, size, 2., UpDirection::Y).is_none());
+    }
+
+    #[test]
+    fn invalid_grids_give_no_terrain() {
+        let size = Vec2::new(10., 10.);
+        let terrain = |heights: Vec<f32>| Terrain::from_heights(size, 2, heights, UpDirection::Y);
+        assert!(terrain(vec![0.; 4]).is_some());
+        assert!(terrain(vec![0.; 2]).is_none());
+        assert!(terrain(vec![0.; 5]).is_none());
+
+        let flat = |spacing: f32| Terrain::from_fn(size, spacing, UpDirection::Y, |_, _| 0.);
+        assert_eq!(flat(1.).map(|terrain| terrain.columns), Some(11));
+        assert!(flat(0.).is_none());
+        assert!(flat(-1.).is_none());
+        assert!(flat(1e-6).is_none());
+    }
+
+    #[test]
+    fn every_seed_gives_random_numbers() {
+        let mut random = XorShift::new(0x9e37_79b9_7f4a_7c15);
+        assert!((0..4).map(|_| random.next_f32()).any(|value| value > 0.));
+    }
 }
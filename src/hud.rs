@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    integrator::{IntegratorStats, Solver},
+    overlay::{toggle_overlay, Overlay, DEFAULT_FONT},
+    playback::Playback,
+};
+
+// Text overlay with the simulation time, the solver and its performance. The rates are averaged
+// over `update_period` seconds of real time:
+//   real time      simulated seconds per real second, below 1 when the simulation lags
+//   steps          integration steps per rendered frame
+//   evaluations    runs of the physics schedule (evaluate_state calls) per second
+// The HUD can be toggled with `toggle_key`.
+
+pub struct HudPlugin {
+    pub font: String,
+    pub toggle_key: KeyCode,
+    pub update_period: f32, // seconds
+}
+
+impl Default for HudPlugin {
+    fn default() -> Self {
+        HudPlugin {
+            font: DEFAULT_FONT.to_string(),
+            toggle_key: KeyCode::H,
+            update_period: 0.5,
+        }
+    }
+}
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Hud {
+            font: self.font.clone(),
+            toggle_key: self.toggle_key,
+            update_period: self.update_period,
+        })
+        .add_startup_system(spawn_hud)
+        .add_system(toggle_overlay::<Hud>)
+        .add_system(update_hud);
+    }
+}
+
+#[derive(Resource)]
+pub struct Hud {
+    pub font: String,
+    pub toggle_key: KeyCode,
+    pub update_period: f32,
+}
+
+impl Overlay for Hud {
+    type Root = HudRoot;
+    fn toggle_key(&self) -> KeyCode {
+        self.toggle_key
+    }
+}
+
+#[derive(Component)]
+pub struct HudRoot;
+
+#[derive(Component)]
+struct HudText;
+
+// totals at the start of the averaging period
+#[derive(Default)]
+struct HudSamples {
+    start: f32,
+    frames: u32,
+    time: Duration,
+    steps: u64,
+    evaluations: u64,
+    resets: u64,
+}
+
+fn spawn_hud(mut commands: Commands, hud: Res<Hud>, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..default()
+                },
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.5).into(),
+            ..default()
+        })
+        .insert(HudRoot)
+        .with_children(|root| {
+            root.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(hud.font.as_str()),
+                    font_size: 14.,
+                    color: Color::WHITE,
+                },
+            ))
+            .insert(HudText);
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_hud(
+    time: Res<Time>,
+    hud: Res<Hud>,
+    solver: Option<Res<Solver>>,
+    fixed_time: Option<Res<FixedTime>>,
+    stats: Option<Res<IntegratorStats>>,
+    playback: Option<Res<Playback>>,
+    mut samples: Local<HudSamples>,
+    mut query: Query<&mut Text, With<HudText>>,
+) {
+    // the integrator was reset, its totals start again from zero
+    if let Some(stats) = &stats {
+        if stats.resets != samples.resets {
+            samples.resets = stats.resets;
+            samples.time = stats.elapsed;
+            samples.steps = stats.steps;
+            samples.evaluations = stats.evaluations;
+        }
+    }
+
+    samples.frames += 1;
+    let now = time.raw_elapsed_seconds();
+    let elapsed = now - samples.start;
+    if elapsed < hud.update_period {
+        return;
+    }
+
+    let mut lines = Vec::new();
+    if let Some(playback) = &playback {
+        lines.push(format!("time        {:.3} s", playback.time));
+        lines.push(format!("replay      {:.2}x", playback.speed));
+    }
+    if let Some(stats) = &stats {
+        let frames = samples.frames as f32;
+        lines.push(format!("time        {:.3} s", stats.time()));
+        if let Some(solver) = &solver {
+            lines.push(format!("solver      {:?}", **solver));
+        }
+        if let Some(fixed_time) = &fixed_time {
+            lines.push(format!(
+                "step        {:.3} ms",
+                fixed_time.period.as_secs_f32() * 1000.
+            ));
+        }
+        lines.push(format!(
+            "real time   {:.2}x",
+            stats.elapsed.saturating_sub(samples.time).as_secs_f32() / elapsed
+        ));
+        lines.push(format!(
+            "steps       {:.1} /frame",
+            stats.steps.saturating_sub(samples.steps) as f32 / frames
+        ));
+        lines.push(format!(
+            "evaluations {:.0} /s",
+            stats.evaluations.saturating_sub(samples.evaluations) as f32 / elapsed
+        ));
+        samples.time = stats.elapsed;
+        samples.steps = stats.steps;
+        samples.evaluations = stats.evaluations;
+    }
+    lines.push(format!(
+        "frame       {:.1} ms",
+        elapsed / samples.frames as f32 * 1000.
+    ));
+    samples.start = now;
+    samples.frames = 0;
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -1,7 +1,8 @@
 use bevy::{ecs::schedule::ScheduleLabel, prelude::*};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     ops::{Add, Mul},
+    time::Duration,
 };
 
 // Define the physics schedule which will be run in the fixed timestep loop
@@ -10,7 +11,7 @@ pub struct PhysicsSchedule;
 
 // Define physics system sets, which are used to group systems together, and define the order in which they are run
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
-enum PhysicsSet {
+pub enum PhysicsSet {
     Initialize,
     Evaluate,
     Finalize,
@@ -36,6 +37,11 @@ impl<T: Stateful> StateMap<T> {
     pub fn insert(&mut self, entity: Entity, state: T::State) {
         self.0.insert(entity, state);
     }
+
+    // true if both maps have states for the same entities
+    pub fn same_entities(&self, other: &StateMap<T>) -> bool {
+        self.0.len() == other.0.len() && self.0.keys().all(|entity| other.0.contains_key(entity))
+    }
 }
 
 impl<T: Stateful> Clone for StateMap<T> {
@@ -69,6 +75,10 @@ impl<T: Stateful> Add for &StateMap<T> {
 }
 
 fn evaluate_state<T: Stateful>(world: &mut World, state: &StateMap<T>, _t: f32) -> StateMap<T> {
+    if let Some(mut stats) = world.get_resource_mut::<IntegratorStats>() {
+        stats.evaluations += 1;
+    }
+
     // assign the state
     world.resource_scope(
         |_world: &mut World, mut physics_state: Mut<PhysicsState<T>>| {
@@ -88,6 +98,11 @@ fn evaluate_state<T: Stateful>(world: &mut World, state: &StateMap<T>, _t: f32)
 }
 
 pub fn integrator_schedule<T: Stateful>(world: &mut World) {
+    // changes since the last step were made outside of the integrator, e.g. a reset of the model
+    // or a different ground, see PhysicsInputs
+    let inputs_changed = world.is_resource_changed::<PhysicsState<T>>()
+        || world.is_resource_changed::<PhysicsInputs>();
+
     // get the initial state
     let state_0 = world
         .get_resource::<PhysicsState<T>>()
@@ -96,29 +111,192 @@ pub fn integrator_schedule<T: Stateful>(world: &mut World) {
         .clone();
 
     // get step size
-    let time_step = world
-        .get_resource::<FixedTime>()
-        .unwrap()
-        .period
-        .as_secs_f32();
+    let period = world.get_resource::<FixedTime>().unwrap().period;
+    let time_step = period.as_secs_f32();
 
-    // get the time (need to track time in the fixed timestep loop)
-    let time = 0.0; // constant for now
+    // get the simulation time, the solvers work in f32 but the clock is kept exact
+    let elapsed = world
+        .get_resource_or_insert_with(IntegratorStats::default)
+        .elapsed;
+    let (time, end_time) = (elapsed.as_secs_f32(), (elapsed + period).as_secs_f32());
 
     // get Solver resource from world
-    let solver = world.get_resource::<Solver>().unwrap();
+    let solver = *world.get_resource::<Solver>().unwrap();
+
+    // previous derivatives are only valid for the solver, step size, bodies and inputs they were
+    // computed with
+    let mut history = world
+        .remove_resource::<SolverHistory<T>>()
+        .unwrap_or_default();
+    if inputs_changed
+        || history.solver != Some(solver)
+        || history.time_step != time_step
+        || history
+            .derivatives
+            .front()
+            .is_some_and(|derivative| !derivative.same_entities(&state_0))
+    {
+        history.derivatives.clear();
+        history.end_derivative = None;
+        history.solver = Some(solver);
+        history.time_step = time_step;
+    }
 
+    // first same as last: the derivative at the end of the last step is the one at the start of
+    // this step, unless the state, the time or the model were changed in between
+    let dstate_0 = match history.end_derivative.take() {
+        Some((t, dstate)) if t == elapsed && !inputs_changed && dstate.same_entities(&state_0) => {
+            dstate
+        }
+        _ => evaluate_state(world, &state_0, time),
+    };
+
+    let (state_0, dstate_0) = (&state_0, &dstate_0);
     let state = match solver {
-        Solver::Euler => euler::<T>(world, &state_0, time, time_step),
-        Solver::Heun => heun::<T>(world, &state_0, time, time_step),
-        Solver::Midpoint => midpoint::<T>(world, &state_0, time, time_step),
-        Solver::RK4 => rk4::<T>(world, &state_0, time, time_step),
+        Solver::Euler => euler::<T>(world, state_0, dstate_0, time, time_step),
+        Solver::Heun => heun::<T>(world, state_0, dstate_0, time, time_step),
+        Solver::Midpoint => midpoint::<T>(world, state_0, dstate_0, time, time_step),
+        Solver::RK4 => rk4::<T>(world, state_0, dstate_0, time, time_step),
+        Solver::AdamsBashforth2 => {
+            adams_bashforth::<T>(world, &mut history, state_0, dstate_0, time, time_step, 2)
+        }
+        Solver::AdamsBashforth3 => {
+            adams_bashforth::<T>(world, &mut history, state_0, dstate_0, time, time_step, 3)
+        }
+        Solver::AdamsBashforth4 => {
+            adams_bashforth::<T>(world, &mut history, state_0, dstate_0, time, time_step, 4)
+        }
+        Solver::AdamsBashforthMoulton => {
+            adams_bashforth_moulton::<T>(world, &mut history, state_0, dstate_0, time, time_step)
+        }
     };
 
+    // evaluate the new state, which leaves the components and the state derivatives consistent
+    // with it, and gives the first derivative of the next step
+    let dstate_1 = evaluate_state(world, &state, end_time);
+    if world.contains_resource::<DenseOutput<T>>() {
+        world.insert_resource(DenseOutput {
+            t0: time,
+            t1: end_time,
+            states: (state_0.clone(), state.clone()),
+            dstates: (dstate_0.clone(), dstate_1.clone()),
+        });
+    }
+    history.end_derivative = Some((elapsed + period, dstate_1));
+    world.insert_resource(history);
+
     let mut physics_state = world.get_resource_mut::<PhysicsState<T>>().unwrap();
+    physics_state.previous_states = state_0.clone();
     physics_state.states = state;
+
+    let mut stats = world.resource_mut::<IntegratorStats>();
+    stats.elapsed += period;
+    stats.steps += 1;
+}
+
+// Dense output of the last integration step: a cubic Hermite interpolant through the states and
+// state derivatives at both ends of the step, for any solver. Insert DenseOutput::<T>::default() to
+// enable it. Both derivatives are evaluated by every step anyway, so it costs no extra evaluations.
+#[derive(Resource)]
+pub struct DenseOutput<T: Stateful> {
+    pub t0: f32,
+    pub t1: f32,
+    pub states: (StateMap<T>, StateMap<T>),
+    pub dstates: (StateMap<T>, StateMap<T>),
+}
+
+impl<T: Stateful> Default for DenseOutput<T> {
+    fn default() -> Self {
+        DenseOutput {
+            t0: 0.,
+            t1: 0.,
+            states: (StateMap::new(), StateMap::new()),
+            dstates: (StateMap::new(), StateMap::new()),
+        }
+    }
 }
 
+impl<T: Stateful> DenseOutput<T> {
+    pub fn contains(&self, t: f32) -> bool {
+        self.t1 > self.t0 && t >= self.t0 && t <= self.t1
+    }
+
+    // interpolated states at time t, None outside of the last step
+    pub fn state_at(&self, t: f32) -> Option<StateMap<T>> {
+        if !self.contains(t) {
+            return None;
+        }
+        let h = self.t1 - self.t0;
+        let s = (t - self.t0) / h;
+        let (s2, s3) = (s * s, s * s * s);
+        let state =
+            &(&self.states.0 * (2. * s3 - 3. * s2 + 1.)) + &(&self.states.1 * (-2. * s3 + 3. * s2));
+        let dstate =
+            &(&self.dstates.0 * ((s3 - 2. * s2 + s) * h)) + &(&self.dstates.1 * ((s3 - s2) * h));
+        Some(&state + &dstate)
+    }
+
+    // Locates the first time in the last step where `event` changes sign (e.g. a height above the
+    // ground), by bisection on the interpolant. Returns the time and the states at that time.
+    pub fn find_event(
+        &self,
+        event: impl Fn(&StateMap<T>) -> f32,
+        tolerance: f32,
+    ) -> Option<(f32, StateMap<T>)> {
+        let (mut t0, mut t1) = (self.t0, self.t1);
+        let value_0 = event(&self.states.0);
+        if self.t1 <= self.t0 || value_0.signum() == event(&self.states.1).signum() {
+            return None;
+        }
+        while t1 - t0 > tolerance {
+            let t = 0.5 * (t0 + t1);
+            if t <= t0 || t >= t1 {
+                break; // the tolerance is below the resolution of f32 at this time
+            }
+            let value = event(&self.state_at(t)?);
+            if value.signum() == value_0.signum() {
+                t0 = t;
+            } else {
+                t1 = t;
+            }
+        }
+        Some((t1, self.state_at(t1)?))
+    }
+}
+
+// Simulation time and running totals of the integrator, e.g. for the HUD. The simulation time adds
+// up the FixedTime period as a Duration, so it doesn't drift like a sum of f32 steps on long runs.
+// Restart the simulation clock with reset(), which counts the resets so the HUD and trails restart
+// with it.
+#[derive(Resource, Default, Debug)]
+pub struct IntegratorStats {
+    pub elapsed: Duration, // simulation time
+    pub steps: u64,        // integration steps taken
+    pub evaluations: u64, // runs of the physics schedule, several per step for higher order solvers
+    pub resets: u64,      // calls of reset()
+}
+
+impl IntegratorStats {
+    // simulation time in seconds
+    pub fn time(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    // restart the simulation time and the totals from zero
+    pub fn reset(&mut self) {
+        *self = IntegratorStats {
+            resets: self.resets + 1,
+            ..default()
+        };
+    }
+}
+
+// Marker for the inputs of the physics schedule other than the state, e.g. the ground or contact
+// parameters. Insert it again or mutate it after changing such an input, so the next step evaluates
+// its first derivative again and the multistep solvers restart.
+#[derive(Resource, Default)]
+pub struct PhysicsInputs;
+
 pub trait Stateful: std::fmt::Debug + 'static {
     type State: Add<Output = Self::State>
         + Mul<f32, Output = Self::State>
@@ -139,6 +317,7 @@ pub trait Stateful: std::fmt::Debug + 'static {
 pub struct PhysicsState<T: Stateful> {
     pub states: StateMap<T>,
     pub dstates: StateMap<T>,
+    pub previous_states: StateMap<T>, // states before the last step, for render interpolation
 }
 
 pub trait PhysicsScheduleExt {
@@ -192,7 +371,11 @@ pub fn initialize_state<T: Component + Stateful>(
         states.insert(entity, joint.get_state());
         dstates.insert(entity, joint.get_dstate());
     }
-    commands.insert_resource(PhysicsState::<T> { states, dstates });
+    commands.insert_resource(PhysicsState::<T> {
+        previous_states: states.clone(),
+        states,
+        dstates,
+    });
 }
 
 fn distribute_state<T: Component + Stateful>(
@@ -217,52 +400,468 @@ fn collect_state_derivatives<T: Component + Stateful>(
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Solver {
     Euler,
     Heun,
     Midpoint,
     RK4,
+    AdamsBashforth2, // explicit multistep methods, one evaluation per step
+    AdamsBashforth3,
+    AdamsBashforth4,
+    AdamsBashforthMoulton, // 4th order predictor-corrector, two evaluations per step
 }
 
-fn euler<T: Stateful>(world: &mut World, state: &StateMap<T>, t: f32, dt: f32) -> StateMap<T> {
-    let state_derivative = evaluate_state(world, &mut state.clone(), t);
-    let updated_state = state + &(&state_derivative * dt);
-    updated_state
+impl Solver {
+    pub const ALL: [Solver; 8] = [
+        Solver::Euler,
+        Solver::Heun,
+        Solver::Midpoint,
+        Solver::RK4,
+        Solver::AdamsBashforth2,
+        Solver::AdamsBashforth3,
+        Solver::AdamsBashforth4,
+        Solver::AdamsBashforthMoulton,
+    ];
+
+    // the next solver in Solver::ALL, wrapping around
+    pub fn next(&self) -> Solver {
+        let index = Solver::ALL
+            .iter()
+            .position(|solver| solver == self)
+            .unwrap();
+        Solver::ALL[(index + 1) % Solver::ALL.len()]
+    }
 }
 
-fn heun<T: Stateful>(world: &mut World, state: &StateMap<T>, t: f32, dt: f32) -> StateMap<T> {
-    let state_derivative = evaluate_state(world, &mut state.clone(), t);
-    let state_derivative2 = evaluate_state(world, &mut (state + &(&state_derivative * dt)), t + dt);
-    state + &(&(&state_derivative + &state_derivative2) * (dt * 0.5))
+// Change the solver or the step size while running. The physics state is kept, so methods can be
+// compared live on the same trajectory.
+pub struct SetSolver(pub Solver);
+pub struct SetTimeStep(pub f32); // seconds
+
+pub fn apply_solver_changes(
+    mut ev_solver: EventReader<SetSolver>,
+    mut ev_time_step: EventReader<SetTimeStep>,
+    mut solver: ResMut<Solver>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    for SetSolver(new_solver) in ev_solver.iter() {
+        *solver = *new_solver;
+    }
+    for SetTimeStep(time_step) in ev_time_step.iter() {
+        // a step that rounds to zero would never advance the fixed timestep loop
+        match Duration::try_from_secs_f32(*time_step) {
+            Ok(period) if !period.is_zero() => fixed_time.period = period,
+            _ => eprintln!("Ignoring invalid time step {} s", time_step),
+        }
+    }
 }
 
-fn midpoint<T: Stateful>(world: &mut World, state: &StateMap<T>, t: f32, dt: f32) -> StateMap<T> {
-    let state_derivative = evaluate_state(world, &mut state.clone(), t);
+// M: cycle through the solvers, [ and ]: halve and double the step size
+pub fn solver_controls(
+    keyboard_input: Res<Input<KeyCode>>,
+    solver: Res<Solver>,
+    fixed_time: Res<FixedTime>,
+    mut ev_solver: EventWriter<SetSolver>,
+    mut ev_time_step: EventWriter<SetTimeStep>,
+) {
+    if keyboard_input.just_pressed(KeyCode::M) {
+        ev_solver.send(SetSolver(solver.next()));
+    }
+    let time_step = fixed_time.period.as_secs_f32();
+    if keyboard_input.just_pressed(KeyCode::LBracket) {
+        ev_time_step.send(SetTimeStep(time_step * 0.5));
+    }
+    if keyboard_input.just_pressed(KeyCode::RBracket) {
+        ev_time_step.send(SetTimeStep(time_step * 2.));
+    }
+}
+
+// The solvers take the state derivative at the start of the step, which integrator_schedule
+// reuses from the end of the previous step.
+
+fn euler<T: Stateful>(
+    _world: &mut World,
+    state: &StateMap<T>,
+    state_derivative: &StateMap<T>,
+    _t: f32,
+    dt: f32,
+) -> StateMap<T> {
+    state + &(state_derivative * dt)
+}
+
+fn heun<T: Stateful>(
+    world: &mut World,
+    state: &StateMap<T>,
+    state_derivative: &StateMap<T>,
+    t: f32,
+    dt: f32,
+) -> StateMap<T> {
+    let state_derivative2 = evaluate_state(world, &(state + &(state_derivative * dt)), t + dt);
+    state + &(&(state_derivative + &state_derivative2) * (dt * 0.5))
+}
+
+fn midpoint<T: Stateful>(
+    world: &mut World,
+    state: &StateMap<T>,
+    state_derivative: &StateMap<T>,
+    t: f32,
+    dt: f32,
+) -> StateMap<T> {
     let state_derivative2 = evaluate_state(
         world,
-        &mut (state + &(&state_derivative * (dt * 0.5))),
+        &(state + &(state_derivative * (dt * 0.5))),
         t + dt * 0.5,
     );
     state + &(&state_derivative2 * dt)
 }
 
-fn rk4<T: Stateful>(world: &mut World, state: &StateMap<T>, t: f32, dt: f32) -> StateMap<T> {
-    let state_derivative = evaluate_state(world, &mut state.clone(), t);
+fn rk4<T: Stateful>(
+    world: &mut World,
+    state: &StateMap<T>,
+    state_derivative: &StateMap<T>,
+    t: f32,
+    dt: f32,
+) -> StateMap<T> {
     let state_derivative2 = evaluate_state(
         world,
-        &mut (state + &(&state_derivative * (dt * 0.5))),
+        &(state + &(state_derivative * (dt * 0.5))),
         t + dt * 0.5,
     );
     let state_derivative3 = evaluate_state(
         world,
-        &mut (state + &(&state_derivative2 * (dt * 0.5))),
+        &(state + &(&state_derivative2 * (dt * 0.5))),
         t + dt * 0.5,
     );
-    let state_derivative4 =
-        evaluate_state(world, &mut (state + &(&state_derivative3 * dt)), t + dt);
-    let state_change = &(&(&state_derivative + &(&state_derivative2 * 2.))
+    let state_derivative4 = evaluate_state(world, &(state + &(&state_derivative3 * dt)), t + dt);
+    let state_change = &(&(state_derivative + &(&state_derivative2 * 2.))
         + &(&state_derivative3 * 2.))
         + &state_derivative4;
     state + &(&state_change * (dt / 6.))
 }
+
+// Derivatives of the previous steps for the multistep solvers, newest first, and the derivative at
+// the end of the last step with its time, which starts the next step
+#[derive(Resource)]
+pub struct SolverHistory<T: Stateful> {
+    pub derivatives: VecDeque<StateMap<T>>,
+    pub end_derivative: Option<(Duration, StateMap<T>)>,
+    pub solver: Option<Solver>,
+    pub time_step: f32,
+}
+
+impl<T: Stateful> Default for SolverHistory<T> {
+    fn default() -> Self {
+        SolverHistory {
+            derivatives: VecDeque::new(),
+            end_derivative: None,
+            solver: None,
+            time_step: 0.,
+        }
+    }
+}
+
+impl<T: Stateful> SolverHistory<T> {
+    // Call after changing an input of the physics schedule other than the state (e.g. a spring
+    // stiffness), so the next step evaluates its first derivative again and the multistep solvers
+    // restart, instead of reusing derivatives from before the change. Systems that don't know the
+    // Stateful type can mark PhysicsInputs as changed instead.
+    pub fn invalidate(&mut self) {
+        self.derivatives.clear();
+        self.end_derivative = None;
+    }
+
+    fn push(&mut self, derivative: StateMap<T>, length: usize) {
+        self.derivatives.push_front(derivative);
+        self.derivatives.truncate(length);
+    }
+}
+
+// coefficients of the current and previous derivatives, newest first
+const ADAMS_BASHFORTH: [&[f32]; 4] = [
+    &[1.],
+    &[3. / 2., -1. / 2.],
+    &[23. / 12., -16. / 12., 5. / 12.],
+    &[55. / 24., -59. / 24., 37. / 24., -9. / 24.],
+];
+// 4th order Adams-Moulton, coefficients of the predicted, current and previous derivatives
+const ADAMS_MOULTON: [f32; 4] = [9. / 24., 19. / 24., -5. / 24., 1. / 24.];
+
+// weighted sum of the current derivative and the previous ones in the history
+fn combine_derivatives<T: Stateful>(
+    coefficients: &[f32],
+    derivative: &StateMap<T>,
+    history: &SolverHistory<T>,
+) -> StateMap<T> {
+    let mut sum = derivative * coefficients[0];
+    for (coefficient, previous) in coefficients[1..].iter().zip(history.derivatives.iter()) {
+        sum = &sum + &(previous * *coefficient);
+    }
+    sum
+}
+
+fn adams_bashforth<T: Stateful>(
+    world: &mut World,
+    history: &mut SolverHistory<T>,
+    state: &StateMap<T>,
+    state_derivative: &StateMap<T>,
+    t: f32,
+    dt: f32,
+    order: usize,
+) -> StateMap<T> {
+    // bootstrap with rk4 until there are enough previous derivatives
+    if history.derivatives.len() < order - 1 {
+        let updated_state = rk4(world, state, state_derivative, t, dt);
+        history.push(state_derivative.clone(), order - 1);
+        return updated_state;
+    }
+
+    let state_change = combine_derivatives(ADAMS_BASHFORTH[order - 1], state_derivative, history);
+    history.push(state_derivative.clone(), order - 1);
+    state + &(&state_change * dt)
+}
+
+// predict with 4th order Adams-Bashforth, then correct with 4th order Adams-Moulton (PECE)
+fn adams_bashforth_moulton<T: Stateful>(
+    world: &mut World,
+    history: &mut SolverHistory<T>,
+    state: &StateMap<T>,
+    state_derivative: &StateMap<T>,
+    t: f32,
+    dt: f32,
+) -> StateMap<T> {
+    if history.derivatives.len() < 3 {
+        let updated_state = rk4(world, state, state_derivative, t, dt);
+        history.push(state_derivative.clone(), 3);
+        return updated_state;
+    }
+
+    let predicted_change = combine_derivatives(ADAMS_BASHFORTH[3], state_derivative, history);
+    let predicted_state = state + &(&predicted_change * dt);
+    let predicted_derivative = evaluate_state(world, &predicted_state, t + dt);
+
+    // the corrector uses the current and the two previous derivatives
+    let mut state_change =
+        &(&predicted_derivative * ADAMS_MOULTON[0]) + &(state_derivative * ADAMS_MOULTON[1]);
+    for (coefficient, previous) in ADAMS_MOULTON[2..].iter().zip(history.derivatives.iter()) {
+        state_change = &state_change + &(previous * *coefficient);
+    }
+    history.push(state_derivative.clone(), 3);
+    state + &(&state_change * dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    // exponential decay, dx/dt = -x
+    #[derive(Component, Debug)]
+    struct Body {
+        x: f32,
+        dx: f32,
+    }
+
+    impl Stateful for Body {
+        type State = f32;
+        fn get_state(&self) -> f32 {
+            self.x
+        }
+        fn set_state(&mut self, state: &f32) {
+            self.x = *state;
+        }
+        fn get_dstate(&self) -> f32 {
+            self.dx
+        }
+        fn set_dstate(&mut self, dstate: f32) {
+            self.dx = dstate;
+        }
+        fn reset(&mut self) {
+            self.dx = 0.;
+        }
+        fn get_name(&self) -> String {
+            "body".to_string()
+        }
+    }
+
+    fn decay(mut query: Query<&mut Body>) {
+        for mut body in query.iter_mut() {
+            body.dx = -body.x;
+        }
+    }
+
+    // world with one decaying body, and a schedule running one integration step
+    fn decay_world(solver: Solver, time_step: f32) -> (World, Schedule, Entity) {
+        let mut world = World::new();
+        world.init_resource::<Schedules>();
+        let entity = world.spawn(Body { x: 1., dx: 0. }).id();
+        let mut states = StateMap::<Body>::new();
+        states.insert(entity, 1.);
+        world.insert_resource(PhysicsState::<Body> {
+            previous_states: states.clone(),
+            dstates: StateMap::new(),
+            states,
+        });
+        world.insert_resource(FixedTime::new_from_secs(time_step));
+        world.insert_resource(solver);
+
+        let mut physics_schedule = Schedule::new();
+        physics_schedule.add_physics_systems::<Body, _, _, _>((), (decay,), ());
+        world.add_schedule(physics_schedule, PhysicsSchedule);
+
+        let mut step = Schedule::new();
+        step.add_system(integrator_schedule::<Body>);
+        (world, step, entity)
+    }
+
+    fn cubic(t: f32) -> f32 {
+        1. - 2. * t + 0.5 * t * t + 0.25 * t * t * t
+    }
+
+    fn dcubic(t: f32) -> f32 {
+        -2. + t + 0.75 * t * t
+    }
+
+    // error of the decay at t = 2 after integrating with the given step size
+    fn decay_error(solver: Solver, time_step: f32) -> f32 {
+        let (mut world, mut step, entity) = decay_world(solver, time_step);
+        let steps = (2. / time_step).round() as usize;
+        for _ in 0..steps {
+            step.run(&mut world);
+        }
+        let x = *world
+            .resource::<PhysicsState<Body>>()
+            .states
+            .get(&entity)
+            .unwrap();
+        (x - (-(steps as f32) * time_step).exp()).abs()
+    }
+
+    #[test]
+    fn multistep_solvers_converge_with_their_order() {
+        for (solver, order) in [
+            (Solver::AdamsBashforth2, 2.),
+            (Solver::AdamsBashforth3, 3.),
+            (Solver::AdamsBashforth4, 4.),
+            (Solver::AdamsBashforthMoulton, 4.),
+        ] {
+            let observed = (decay_error(solver, 0.2) / decay_error(solver, 0.1)).log2();
+            assert!(
+                (observed - order).abs() < 0.5,
+                "{:?} converges with order {}",
+                solver,
+                observed
+            );
+        }
+    }
+
+    #[test]
+    fn history_is_cleared_when_the_bodies_change() {
+        let (mut world, mut step, entity) = decay_world(Solver::AdamsBashforth4, 0.01);
+        for _ in 0..4 {
+            step.run(&mut world);
+        }
+        let history_length =
+            |world: &World| world.resource::<SolverHistory<Body>>().derivatives.len();
+        assert_eq!(history_length(&world), 3);
+
+        // same number of bodies, but a different one
+        world.despawn(entity);
+        let other = world.spawn(Body { x: 1., dx: 0. }).id();
+        let mut states = StateMap::<Body>::new();
+        states.insert(other, 1.);
+        world.resource_mut::<PhysicsState<Body>>().states = states;
+        step.run(&mut world);
+        assert_eq!(history_length(&world), 1);
+    }
+
+    #[test]
+    fn history_is_cleared_when_the_state_is_reset() {
+        let (mut world, mut step, entity) = decay_world(Solver::AdamsBashforth4, 0.01);
+        for _ in 0..4 {
+            step.run(&mut world);
+        }
+
+        world
+            .resource_mut::<PhysicsState<Body>>()
+            .states
+            .insert(entity, 2.);
+        step.run(&mut world);
+        assert_eq!(world.resource::<SolverHistory<Body>>().derivatives.len(), 1);
+        // the step restarts with rk4 from the new state, without the derivatives of the old one
+        let x = *world
+            .resource::<PhysicsState<Body>>()
+            .states
+            .get(&entity)
+            .unwrap();
+        assert!((x - 2. * (-0.01_f32).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn dense_output_reproduces_a_cubic() {
+        let entity = Entity::from_raw(0);
+        let (t0, t1) = (0.5, 1.5);
+        let state_map = |value: f32| {
+            let mut states = StateMap::<Body>::new();
+            states.insert(entity, value);
+            states
+        };
+        let dense_output = DenseOutput::<Body> {
+            t0,
+            t1,
+            states: (state_map(cubic(t0)), state_map(cubic(t1))),
+            dstates: (state_map(dcubic(t0)), state_map(dcubic(t1))),
+        };
+
+        let value_at = |t: f32| dense_output.state_at(t).unwrap().get(&entity).copied();
+        assert_eq!(value_at(t0), Some(cubic(t0)));
+        assert_eq!(value_at(t1), Some(cubic(t1)));
+        for i in 1..10 {
+            let t = t0 + (t1 - t0) * i as f32 / 10.;
+            assert!((value_at(t).unwrap() - cubic(t)).abs() < 1e-5, "t = {}", t);
+        }
+        assert!(dense_output.state_at(t1 + 0.1).is_none());
+    }
+
+    #[test]
+    fn first_derivative_is_reused_until_the_inputs_change() {
+        let (mut world, mut step, entity) = decay_world(Solver::RK4, 0.01);
+        let evaluations = |world: &World| world.resource::<IntegratorStats>().evaluations;
+
+        // the first step also evaluates its start
+        step.run(&mut world);
+        assert_eq!(evaluations(&world), 5);
+        step.run(&mut world);
+        assert_eq!(evaluations(&world), 9);
+
+        world
+            .resource_mut::<PhysicsState<Body>>()
+            .states
+            .insert(entity, 2.);
+        step.run(&mut world);
+        assert_eq!(evaluations(&world), 14);
+        // the state after the step matches the restarted decay, not the cached derivative
+        let x = *world
+            .resource::<PhysicsState<Body>>()
+            .states
+            .get(&entity)
+            .unwrap();
+        assert!((x - 2. * (-0.01_f32).exp()).abs() < 1e-6);
+
+        world.insert_resource(FixedTime::new(Duration::from_millis(20)));
+        step.run(&mut world);
+        assert_eq!(evaluations(&world), 19);
+
+        world.insert_resource(PhysicsInputs);
+        step.run(&mut world);
+        assert_eq!(evaluations(&world), 24);
+
+        world.resource_mut::<SolverHistory<Body>>().invalidate();
+        step.run(&mut world);
+        assert_eq!(evaluations(&world), 29);
+
+        step.run(&mut world);
+        assert_eq!(evaluations(&world), 33);
+    }
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::integrator::{DenseOutput, PhysicsState, Stateful};
+
+// Smooth rendering between fixed physics steps. Frames rarely line up with the fixed timestep, so
+// copying the latest integrated state into the transforms makes the motion stutter. Instead the
+// transforms are drawn one step behind, at the fraction of the step given by the time accumulated
+// in FixedTime.
+
+pub trait StateTransform: Stateful {
+    // write the state of a body into its transform
+    fn state_transform(state: &Self::State, transform: &mut Transform);
+}
+
+// fraction of the next fixed step that has already elapsed in real time
+pub fn overstep_fraction(fixed_time: &FixedTime) -> f32 {
+    (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).clamp(0., 1.)
+}
+
+// transforms blended linearly between the states before and after the last step
+pub fn interpolate_transforms<T: Component + StateTransform>(
+    fixed_time: Res<FixedTime>,
+    physics_state: Option<Res<PhysicsState<T>>>,
+    mut query: Query<(Entity, &mut Transform), With<T>>,
+) {
+    let Some(physics_state) = physics_state else {
+        return;
+    };
+    let alpha = overstep_fraction(&fixed_time);
+    for (entity, mut transform) in query.iter_mut() {
+        let Some(state) = physics_state.states.get(&entity) else {
+            continue;
+        };
+        match physics_state.previous_states.get(&entity) {
+            Some(previous_state) => {
+                let blended = previous_state.clone() * (1. - alpha) + state.clone() * alpha;
+                T::state_transform(&blended, &mut transform);
+            }
+            None => T::state_transform(state, &mut transform),
+        }
+    }
+}
+
+// transforms from the cubic interpolant of the last step, needs DenseOutput<T> to be inserted
+pub fn dense_output_transforms<T: Component + StateTransform>(
+    fixed_time: Res<FixedTime>,
+    dense_output: Option<Res<DenseOutput<T>>>,
+    mut query: Query<(Entity, &mut Transform), With<T>>,
+) {
+    let Some(dense_output) = dense_output else {
+        return;
+    };
+    let t = dense_output.t0 + overstep_fraction(&fixed_time) * (dense_output.t1 - dense_output.t0);
+    let Some(states) = dense_output.state_at(t) else {
+        return;
+    };
+    for (entity, mut transform) in query.iter_mut() {
+        if let Some(state) = states.get(&entity) {
+            T::state_transform(state, &mut transform);
+        }
+    }
+}
@@ -1,4 +1,16 @@
 pub mod camera_az_el;
+pub mod camera_path;
+pub mod compare;
+pub mod contact;
+pub mod debug_draw;
 pub mod environment;
+pub mod hud;
 pub mod integrator;
+pub mod interpolation;
+pub mod overlay;
+pub mod picking;
+pub mod playback;
+pub mod plot;
 pub mod recorder;
+pub mod telemetry;
+pub mod trail;
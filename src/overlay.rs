@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+// Shared parts of the ui overlays (HUD and plot). Bevy has no built-in font, so an overlay's font
+// must be a font file in the assets folder. The default is the DejaVu Sans Mono font shipped in
+// assets/fonts.
+
+pub const DEFAULT_FONT: &str = "fonts/DejaVuSansMono.ttf";
+
+// overlay resource whose root ui node is shown and hidden with a key
+pub trait Overlay: Resource {
+    type Root: Component;
+    fn toggle_key(&self) -> KeyCode;
+}
+
+pub fn toggle_overlay<O: Overlay>(
+    keyboard_input: Res<Input<KeyCode>>,
+    overlay: Res<O>,
+    mut query: Query<&mut Visibility, With<O::Root>>,
+) {
+    if keyboard_input.just_pressed(overlay.toggle_key()) {
+        for mut visibility in query.iter_mut() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Inherited,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use bevy::{prelude::*, render::primitives::Aabb, window::PrimaryWindow};
+
+use crate::camera_az_el::{camera_under_cursor, AzElCamera};
+
+// CPU side picking against mesh bounding boxes. Double clicking an entity re-centers the camera under
+// the cursor on the hit point, and sends a Picked event that other systems can use.
+
+pub struct Picked(pub Entity);
+
+const PICK_BUTTON: MouseButton = MouseButton::Left;
+const DOUBLE_CLICK_TIME: f64 = 0.3; // seconds
+const FOCUS_TRANSITION: f32 = 0.3; // seconds
+
+// distance along the ray to the first intersection with a bounding box, if any
+pub fn ray_aabb_intersection(ray: &Ray, aabb: &Aabb, transform: &GlobalTransform) -> Option<f32> {
+    // intersect in the local space of the box, the distance along the ray is unchanged
+    let world_to_local = transform.affine().inverse();
+    let origin = world_to_local.transform_point3(ray.origin);
+    let direction = world_to_local.transform_vector3(ray.direction);
+
+    let min = Vec3::from(aabb.center - aabb.half_extents);
+    let max = Vec3::from(aabb.center + aabb.half_extents);
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+    for axis in 0..3 {
+        if direction[axis].abs() < f32::EPSILON {
+            // parallel to the slab, miss unless the origin is inside it
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t0 = (min[axis] - origin[axis]) / direction[axis];
+        let t1 = (max[axis] - origin[axis]) / direction[axis];
+        t_near = t_near.max(t0.min(t1));
+        t_far = t_far.min(t0.max(t1));
+    }
+    if t_near > t_far || t_far < 0. {
+        return None;
+    }
+    Some(t_near.max(0.))
+}
+
+// nearest visible entity hit by a ray, and the distance to it
+pub fn cast_ray<'a>(
+    ray: &Ray,
+    bounds: impl Iterator<
+        Item = (
+            Entity,
+            &'a Aabb,
+            &'a GlobalTransform,
+            &'a ComputedVisibility,
+        ),
+    >,
+) -> Option<(Entity, f32)> {
+    bounds
+        .filter(|(_, _, _, visibility)| visibility.is_visible())
+        .filter_map(|(entity, aabb, transform, _)| {
+            ray_aabb_intersection(ray, aabb, transform).map(|t| (entity, t))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pick_on_double_click(
+    time: Res<Time>,
+    input_mouse: Res<Input<MouseButton>>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    mut last_click: Local<Option<f64>>,
+    mut cameras: Query<(Entity, &Camera, &GlobalTransform, &mut AzElCamera)>,
+    bounds: Query<(Entity, &Aabb, &GlobalTransform, &ComputedVisibility)>,
+    mut ev_picked: EventWriter<Picked>,
+) {
+    if !input_mouse.just_pressed(PICK_BUTTON) {
+        return;
+    }
+    let now = time.raw_elapsed_seconds_f64();
+    let double_click = last_click.is_some_and(|last| now - last < DOUBLE_CLICK_TIME);
+    *last_click = if double_click { None } else { Some(now) };
+    if !double_click {
+        return;
+    }
+
+    let Some((camera_entity, cursor)) = camera_under_cursor(
+        cameras.iter().map(|(entity, camera, ..)| (entity, camera)),
+        &windows,
+        &primary_window,
+    ) else {
+        return;
+    };
+    let Ok((_, camera, camera_transform, mut az_el)) = cameras.get_mut(camera_entity) else {
+        return;
+    };
+    let Some(viewport_size) = camera.logical_viewport_size() else {
+        return;
+    };
+    // viewport_to_world expects the origin at the bottom left of the viewport
+    let cursor = Vec2::new(cursor.x, viewport_size.y - cursor.y);
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    if let Some((entity, distance)) = cast_ray(&ray, bounds.iter()) {
+        let mut view = az_el.view();
+        view.focus = ray.origin + ray.direction * distance;
+        az_el.animate_to(view, FOCUS_TRANSITION);
+        ev_picked.send(Picked(entity));
+    }
+}
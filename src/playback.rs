@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+
+use crate::{integrator::Stateful, recorder::RecordedData};
+
+// Playback replays a recorded run instead of integrating. The recorded state and state derivative
+// of each component are interpolated at the playback time and pushed back into the components, so
+// the rest of the app (transforms, camera, etc.) works exactly as it does for a live simulation.
+
+// Components that can be driven from recorded data. The recorder stores the state and state
+// derivative of each component as f32 values (see Stateful::State: Into<f32>), this maps them back.
+pub trait Replayable: Stateful {
+    fn set_recorded_state(&mut self, state: f32, dstate: f32);
+}
+
+#[derive(Resource)]
+pub struct Playback {
+    pub time: f32,
+    pub speed: f32,
+    pub paused: bool,
+    pub looping: bool,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            time: 0.,
+            speed: 1.,
+            paused: false,
+            looping: true,
+        }
+    }
+}
+
+pub fn playback_system<T: Component + Replayable>(
+    time: Res<Time>,
+    mut playback: ResMut<Playback>,
+    recorded_data: Res<RecordedData>,
+    mut query: Query<&mut T>,
+) {
+    let Some((start_time, end_time)) = recorded_data.time_range() else {
+        return;
+    };
+
+    // a new recording starts from its first row, which isn't at zero when recording started later
+    if recorded_data.is_changed() {
+        playback.time = start_time;
+    }
+
+    // advance the playback time
+    if !playback.paused {
+        playback.time += time.delta_seconds() * playback.speed;
+    }
+    if playback.time > end_time {
+        playback.time = if playback.looping {
+            start_time
+        } else {
+            end_time
+        };
+    } else if playback.time < start_time {
+        playback.time = if playback.looping {
+            end_time
+        } else {
+            start_time
+        };
+    }
+
+    // push the recorded states back into the components
+    for mut joint in query.iter_mut() {
+        let name = joint.get_name();
+        let state = recorded_data.value_at(&format!("{}_state", name), playback.time);
+        let dstate = recorded_data.value_at(&format!("{}_dstate", name), playback.time);
+        if let (Some(state), Some(dstate)) = (state, dstate) {
+            joint.set_recorded_state(state, dstate);
+        }
+    }
+}
+
+pub fn playback_controls(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut playback: ResMut<Playback>,
+    recorded_data: Res<RecordedData>,
+) {
+    // change key mapping for playback control here
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        playback.paused = !playback.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        playback.speed *= 2.;
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        playback.speed *= 0.5;
+    }
+    // restart from the first recorded row
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        if let Some((start_time, _)) = recorded_data.time_range() {
+            playback.time = start_time;
+        }
+    }
+    // scrub through the recording while paused, at the playback speed
+    if playback.paused {
+        let step = time.delta_seconds() * playback.speed;
+        if keyboard_input.pressed(KeyCode::Right) {
+            playback.time += step;
+        }
+        if keyboard_input.pressed(KeyCode::Left) {
+            playback.time -= step;
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use bevy::prelude::*;
+
+use crate::{
+    overlay::{toggle_overlay, Overlay, DEFAULT_FONT},
+    recorder::Recorder,
+};
+
+// Strip-chart overlay of recorded channels. Each axis shows the last `window` seconds of its
+// channels from the recorder's in-memory buffer, autoscaled to the visible data. The recorder keeps
+// a row per fixed step, so the buffer is grown to hold `window` seconds at the current step size.
+// The plot is drawn with ui nodes (one small node per sample), and can be toggled with `toggle_key`.
+
+pub struct PlotPlugin {
+    pub axes: Vec<Vec<String>>, // channel names for each axis, e.g. vec![vec!["cube_state".to_string()]]
+    pub window: f32,            // seconds of history shown
+    pub points: usize,          // maximum number of samples drawn per channel
+    pub font: String,           // font asset used for the legend and scale labels
+    pub toggle_key: KeyCode,
+}
+
+impl Default for PlotPlugin {
+    fn default() -> Self {
+        PlotPlugin {
+            axes: Vec::new(),
+            window: 10.,
+            points: 200,
+            font: DEFAULT_FONT.to_string(),
+            toggle_key: KeyCode::P,
+        }
+    }
+}
+
+impl Plugin for PlotPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Plot {
+            axes: self.axes.clone(),
+            window: self.window,
+            points: self.points,
+            font: self.font.clone(),
+            toggle_key: self.toggle_key,
+        })
+        .add_startup_system(spawn_plot)
+        .add_system(toggle_overlay::<Plot>)
+        .add_system(size_recorder_buffer.before(update_plot))
+        .add_system(update_plot);
+    }
+}
+
+#[derive(Resource)]
+pub struct Plot {
+    pub axes: Vec<Vec<String>>,
+    pub window: f32,
+    pub points: usize,
+    pub font: String,
+    pub toggle_key: KeyCode,
+}
+
+impl Overlay for Plot {
+    type Root = PlotRoot;
+    fn toggle_key(&self) -> KeyCode {
+        self.toggle_key
+    }
+}
+
+#[derive(Component)]
+pub struct PlotRoot;
+
+#[derive(Component)]
+struct PlotPoint {
+    axis: usize,
+    channel: usize,
+    index: usize,
+}
+
+#[derive(Component)]
+struct PlotLabel {
+    axis: usize,
+    max: bool,
+}
+
+const PLOT_COLORS: [Color; 6] = [
+    Color::rgb(0.9, 0.2, 0.2),
+    Color::rgb(0.2, 0.6, 0.9),
+    Color::rgb(0.2, 0.8, 0.3),
+    Color::rgb(0.9, 0.7, 0.1),
+    Color::rgb(0.7, 0.3, 0.9),
+    Color::rgb(0.1, 0.8, 0.8),
+];
+
+fn channel_color(channel: usize) -> Color {
+    PLOT_COLORS[channel % PLOT_COLORS.len()]
+}
+
+fn spawn_plot(mut commands: Commands, plot: Res<Plot>, asset_server: Res<AssetServer>) {
+    let font = asset_server.load(plot.font.as_str());
+    let text_style = |color: Color| TextStyle {
+        font: font.clone(),
+        font_size: 12.,
+        color,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.),
+                    top: Val::Px(10.),
+                    ..default()
+                },
+                size: Size::width(Val::Px(420.)),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.5).into(),
+            ..default()
+        })
+        .insert(PlotRoot)
+        .with_children(|root| {
+            for (axis, channels) in plot.axes.iter().enumerate() {
+                // axis area with the samples and scale labels
+                root.spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.), Val::Px(140.)),
+                        margin: UiRect::top(Val::Px(6.)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                    ..default()
+                })
+                .with_children(|area| {
+                    for (channel, _) in channels.iter().enumerate() {
+                        for index in 0..plot.points {
+                            area.spawn(NodeBundle {
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    size: Size::new(Val::Px(3.), Val::Px(3.)),
+                                    ..default()
+                                },
+                                background_color: channel_color(channel).into(),
+                                visibility: Visibility::Hidden,
+                                ..default()
+                            })
+                            .insert(PlotPoint {
+                                axis,
+                                channel,
+                                index,
+                            });
+                        }
+                    }
+                    for max in [true, false] {
+                        let position = if max {
+                            UiRect::new(Val::Px(2.), Val::Auto, Val::Px(2.), Val::Auto)
+                        } else {
+                            UiRect::new(Val::Px(2.), Val::Auto, Val::Auto, Val::Px(2.))
+                        };
+                        area.spawn(
+                            TextBundle::from_section("", text_style(Color::GRAY)).with_style(
+                                Style {
+                                    position_type: PositionType::Absolute,
+                                    position,
+                                    ..default()
+                                },
+                            ),
+                        )
+                        .insert(PlotLabel { axis, max });
+                    }
+                });
+
+                // legend
+                root.spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|legend| {
+                    for (channel, name) in channels.iter().enumerate() {
+                        legend.spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(10.), Val::Px(10.)),
+                                margin: UiRect::new(
+                                    Val::Px(6.),
+                                    Val::Px(3.),
+                                    Val::Px(0.),
+                                    Val::Px(0.),
+                                ),
+                                ..default()
+                            },
+                            background_color: channel_color(channel).into(),
+                            ..default()
+                        });
+                        legend.spawn(TextBundle::from_section(
+                            name.as_str(),
+                            text_style(Color::WHITE),
+                        ));
+                    }
+                });
+            }
+        });
+}
+
+// keep enough rows in the recorder buffer for the plot window
+fn size_recorder_buffer(
+    recorder: Option<NonSendMut<Recorder>>,
+    plot: Res<Plot>,
+    fixed_time: Option<Res<FixedTime>>,
+) {
+    let (Some(mut recorder), Some(fixed_time)) = (recorder, fixed_time) else {
+        return;
+    };
+    let rows = (plot.window / fixed_time.period.as_secs_f32()).ceil() as usize + 1;
+    if recorder.buffer_length() < rows {
+        recorder.set_buffer_length(rows);
+    }
+}
+
+fn update_plot(
+    recorder: Option<NonSend<Recorder>>,
+    plot: Res<Plot>,
+    mut points: Query<(&PlotPoint, &mut Style, &mut Visibility)>,
+    mut labels: Query<(&PlotLabel, &mut Text)>,
+) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    let Some(last_row) = recorder.buffer().back() else {
+        return;
+    };
+    let start_time = last_row[0] - plot.window;
+
+    // collect the visible samples of each channel, and the range of each axis
+    let mut series = Vec::<Vec<Vec<(f32, f32)>>>::new();
+    let mut ranges = Vec::<(f32, f32)>::new();
+    for channels in plot.axes.iter() {
+        let mut axis_series = Vec::new();
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        for name in channels.iter() {
+            let mut samples = Vec::new();
+            if let Some(column) = recorder.column_index(name) {
+                let rows: Vec<&Vec<f32>> = recorder
+                    .buffer()
+                    .iter()
+                    .filter(|row| row[0] >= start_time)
+                    .collect();
+                let points = plot.points.max(1);
+                let stride = rows.len().div_ceil(points);
+                for row in rows.iter().step_by(stride.max(1)) {
+                    let value = row[column];
+                    if value.is_finite() {
+                        min = min.min(value);
+                        max = max.max(value);
+                        samples.push((row[0], value));
+                    }
+                }
+            }
+            axis_series.push(samples);
+        }
+
+        // autoscale, with a little padding so the traces don't touch the edges
+        if !min.is_finite() {
+            (min, max) = (-1., 1.);
+        } else if max - min < 1e-6 {
+            (min, max) = (min - 1., max + 1.);
+        } else {
+            let padding = (max - min) * 0.05;
+            (min, max) = (min - padding, max + padding);
+        }
+        series.push(axis_series);
+        ranges.push((min, max));
+    }
+
+    for (point, mut style, mut visibility) in points.iter_mut() {
+        let sample = series
+            .get(point.axis)
+            .and_then(|axis_series| axis_series.get(point.channel))
+            .and_then(|samples| samples.get(point.index));
+        if let Some((t, value)) = sample {
+            let (min, max) = ranges[point.axis];
+            style.position.left = Val::Percent((t - start_time) / plot.window * 100.);
+            style.position.bottom = Val::Percent((value - min) / (max - min) * 100.);
+            *visibility = Visibility::Inherited;
+        } else {
+            *visibility = Visibility::Hidden;
+        }
+    }
+
+    for (label, mut text) in labels.iter_mut() {
+        let (min, max) = ranges[label.axis];
+        text.sections[0].value = format!("{:.3}", if label.max { max } else { min });
+    }
+}
@@ -1,35 +1,218 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-use crate::integrator::Stateful;
+use crate::integrator::{IntegratorStats, PhysicsState, Stateful};
 use bevy::prelude::*;
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
 
-#[derive(Debug)]
 pub struct Recorder {
     conn: Connection,
     insert_stmt: Option<String>,
+    channels: Vec<Channel>,
+    columns: Vec<String>,
+    entities: Vec<(Entity, Vec<usize>)>, // recorded entities and their component channels, per column
+    world_channels: Vec<usize>,          // recorded world channels, per column
+    last_row: Vec<f32>,
+    buffer: VecDeque<Vec<f32>>, // the most recent rows, kept in memory for plotting
+    buffer_length: usize,
+    frame: Option<std::time::Instant>, // Time::last_update of the frame whose rows are pending
 }
 
-pub fn recorder_system<T: Component + Stateful>(
-    mut recorder: NonSendMut<Recorder>,
-    time: Res<Time>,
-    query: Query<&T>,
-) {
-    insert_data(&mut recorder, &query, time.elapsed_seconds() as f32);
+type ComponentChannel = Box<dyn Fn(&World, Entity) -> Option<f32>>;
+
+// user defined signals recorded alongside the states
+enum Channel {
+    // one column per recorded entity that has the channel's component when the table is created,
+    // named "<entity name>_<channel name>"
+    Component {
+        name: String,
+        value: ComponentChannel,
+    },
+    // a single column sampled from the world
+    World {
+        name: String,
+        value: Box<dyn Fn(&mut World) -> f32>,
+    },
 }
 
-pub fn initialize_recorder<T: Component + Stateful>(
-    mut recorder: NonSendMut<Recorder>,
-    query: Query<&T>,
-) {
-    create_table(&mut recorder, &query);
-    insert_data(&mut recorder, &query, 0.0)
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let channel_names: Vec<&String> = self
+            .channels
+            .iter()
+            .map(|channel| match channel {
+                Channel::Component { name, .. } | Channel::World { name, .. } => name,
+            })
+            .collect();
+        f.debug_struct("Recorder")
+            .field("conn", &self.conn)
+            .field("insert_stmt", &self.insert_stmt)
+            .field("channels", &channel_names)
+            .field("columns", &self.columns)
+            .finish()
+    }
 }
 
-fn create_table<T: Component + Stateful>(recorder: &mut Recorder, query: &Query<&T>) {
+impl Recorder {
+    fn new(conn: Connection) -> Self {
+        Recorder {
+            conn,
+            insert_stmt: None,
+            channels: Vec::new(),
+            columns: Vec::new(),
+            entities: Vec::new(),
+            world_channels: Vec::new(),
+            last_row: Vec::new(),
+            buffer: VecDeque::new(),
+            buffer_length: 10_000,
+            frame: None,
+        }
+    }
+
+    // recorder without a file, e.g. to only stream or plot the channels
+    pub fn in_memory() -> Self {
+        Recorder::new(Connection::open_in_memory().unwrap())
+    }
+
+    // record a signal derived from a component C of each recorded entity, e.g.
+    // recorder.add_channel("force", |joint: &Joint| joint.force);
+    // C can be the recorded Stateful component or any other component on the same entities.
+    // channels must be added before the recorder is initialized
+    pub fn add_channel<C: Component>(
+        &mut self,
+        name: &str,
+        channel: impl Fn(&C) -> f32 + 'static,
+    ) -> &mut Self {
+        self.channels.push(Channel::Component {
+            name: name.to_string(),
+            value: Box::new(move |world: &World, entity: Entity| {
+                world.get::<C>(entity).map(&channel)
+            }),
+        });
+        self
+    }
+
+    // record a signal computed from the world, e.g. the total energy of the system. The world is
+    // mutable so the channel can run queries
+    pub fn add_world_channel(
+        &mut self,
+        name: &str,
+        channel: impl Fn(&mut World) -> f32 + 'static,
+    ) -> &mut Self {
+        self.channels.push(Channel::World {
+            name: name.to_string(),
+            value: Box::new(channel),
+        });
+        self
+    }
+
+    // names of the recorded columns, starting with "time"
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    // the most recently recorded row, in the same order as the columns
+    pub fn last_row(&self) -> &[f32] {
+        &self.last_row
+    }
+
+    // the most recently recorded rows, oldest first
+    pub fn buffer(&self) -> &VecDeque<Vec<f32>> {
+        &self.buffer
+    }
+
+    pub fn buffer_length(&self) -> usize {
+        self.buffer_length
+    }
+
+    pub fn set_buffer_length(&mut self, rows: usize) -> &mut Self {
+        self.buffer_length = rows;
+        while self.buffer.len() > rows {
+            self.buffer.pop_front();
+        }
+        self
+    }
+
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column == name)
+    }
+
+    // write the rows inserted since the last commit to the database
+    pub fn commit(&mut self) -> rusqlite::Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(error) = self.commit() {
+            eprintln!("Recorder failed to commit data: {}", error);
+        }
+    }
+}
+
+// the integrator's simulation time, or the app time when there is no integrator (e.g. replaying)
+fn recording_time(world: &World) -> f32 {
+    match world.get_resource::<IntegratorStats>() {
+        Some(stats) => stats.time(),
+        None => world.resource::<Time>().elapsed_seconds(),
+    }
+}
+
+// Records a row per integration step, at the simulation time. Add it to the fixed timestep loop
+// after the integrator, i.e.
+// recorder_system::<T>.in_schedule(CoreSchedule::FixedUpdate).after(integrator_schedule::<T>)
+// The rows are inserted in a transaction per frame. The first step of a frame commits the rows of
+// the previous frames. Add commit_recorder (e.g. in CoreSet::Last) to also keep the rows of the last
+// frame, the app can exit without dropping the Recorder.
+pub fn recorder_system<T: Component + Stateful>(world: &mut World) {
+    let time = recording_time(world);
+    let frame = world.resource::<Time>().last_update();
+    let mut recorder = world.remove_non_send_resource::<Recorder>().unwrap();
+    if recorder.frame != frame {
+        if let Err(error) = recorder.commit() {
+            eprintln!("Recorder failed to commit data: {}", error);
+        }
+        recorder.frame = frame;
+    }
+    if let Err(error) = insert_data::<T>(&mut recorder, world, time) {
+        eprintln!("Recorder failed to insert data: {}", error);
+    }
+    world.insert_non_send_resource(recorder);
+}
+
+// commit the rows recorded in this frame, e.g. in CoreSet::Last
+pub fn commit_recorder(mut recorder: NonSendMut<Recorder>) {
+    if let Err(error) = recorder.commit() {
+        eprintln!("Recorder failed to commit data: {}", error);
+    }
+}
+
+pub fn initialize_recorder<T: Component + Stateful>(world: &mut World) {
+    let mut recorder = world.remove_non_send_resource::<Recorder>().unwrap();
+    let time = recording_time(world);
+    let result = create_table::<T>(&mut recorder, world)
+        .and_then(|_| insert_data::<T>(&mut recorder, world, time));
+    if let Err(error) = result {
+        // keep the app running, without recording
+        eprintln!(
+            "Recorder failed to create the data table, not recording: {}",
+            error
+        );
+        recorder.insert_stmt = None;
+    }
+    world.insert_non_send_resource(recorder);
+}
+
+fn create_table<T: Component + Stateful>(
+    recorder: &mut Recorder,
+    world: &mut World,
+) -> rusqlite::Result<()> {
     // build sql table
     let mut sql_table_defenition = String::new();
-    sql_table_defenition.push_str("CREATE TABLE IF NOT EXISTS data (");
+    sql_table_defenition.push_str("CREATE TABLE data (");
     sql_table_defenition.push_str("time REAL, ");
 
     let mut sql_table_insert = String::new();
@@ -37,18 +220,60 @@ fn create_table<T: Component + Stateful>(recorder: &mut Recorder, query: &Query<
     sql_table_insert.push_str("time, ");
 
     // add state and dstate columns
-    let mut sql_params = Vec::<f32>::new();
-    sql_params.push(0.0);
-    for joint in query.iter() {
+    let mut column_names = Vec::<String>::new();
+    let mut matched_channels = vec![false; recorder.channels.len()];
+    let mut entities = Vec::new();
+    let mut query = world.query::<(Entity, &T)>();
+    for (entity, joint) in query.iter(world) {
         let name = joint.get_name();
         println!("{}", name);
-        let state_name = format!("{}_state", joint.get_name());
-        let dstate_name = format!("{}_dstate", joint.get_name());
-        sql_table_defenition.push_str(&format!("{} REAL, ", state_name));
-        sql_table_defenition.push_str(&format!("{} REAL, ", dstate_name));
-        sql_table_insert.push_str(&format!("{}, {}, ", state_name, dstate_name));
-        sql_params.push(joint.get_state().into());
-        sql_params.push(joint.get_dstate().into());
+        column_names.push(format!("{}_state", name));
+        column_names.push(format!("{}_dstate", name));
+
+        // add component channel columns
+        let mut entity_channels = Vec::new();
+        for (index, channel) in recorder.channels.iter().enumerate() {
+            if let Channel::Component {
+                name: channel_name,
+                value,
+            } = channel
+            {
+                let column_name = format!("{}_{}", name, channel_name);
+                if value(world, entity).is_some() && add_column(&mut column_names, column_name) {
+                    matched_channels[index] = true;
+                    entity_channels.push(index);
+                }
+            }
+        }
+        entities.push((entity, entity_channels));
+    }
+
+    // a channel over a component that none of the recorded entities have would silently record nothing
+    for (channel, matched) in recorder.channels.iter().zip(matched_channels) {
+        if let Channel::Component { name, .. } = channel {
+            if !matched {
+                eprintln!(
+                    "Recorder channel \"{}\" matches no entity with the recorded component",
+                    name
+                );
+            }
+        }
+    }
+
+    // add world channel columns
+    let mut world_channels = Vec::new();
+    for (index, channel) in recorder.channels.iter().enumerate() {
+        if let Channel::World { name, .. } = channel {
+            if add_column(&mut column_names, name.clone()) {
+                world_channels.push(index);
+            }
+        }
+    }
+
+    // channel names are chosen by the user, so they are quoted
+    for column_name in column_names.iter() {
+        sql_table_defenition.push_str(&format!("{} REAL, ", quote_identifier(column_name)));
+        sql_table_insert.push_str(&format!("{}, ", quote_identifier(column_name)));
     }
 
     // remove last comma
@@ -60,8 +285,8 @@ fn create_table<T: Component + Stateful>(recorder: &mut Recorder, query: &Query<
     sql_table_insert.pop();
     sql_table_insert.push_str(") VALUES (");
     sql_table_insert.push_str("?, ");
-    for _ in query.iter() {
-        sql_table_insert.push_str("?, ?, ");
+    for _ in column_names.iter() {
+        sql_table_insert.push_str("?, ");
     }
     sql_table_insert.pop();
     sql_table_insert.pop();
@@ -70,50 +295,121 @@ fn create_table<T: Component + Stateful>(recorder: &mut Recorder, query: &Query<
     println!("{}", sql_table_defenition);
     println!("{}", sql_table_insert);
 
-    // create table
-    recorder
-        .conn
-        .execute(sql_table_defenition.as_str(), ())
-        .unwrap();
+    // create table, replacing the previous run so runs don't append to each other and the columns
+    // always match the current channels
+    recorder.conn.execute("DROP TABLE IF EXISTS data", ())?;
+    recorder.conn.execute(sql_table_defenition.as_str(), ())?;
 
     // insert data
     recorder.insert_stmt = Some(sql_table_insert);
+    recorder.entities = entities;
+    recorder.world_channels = world_channels;
+    recorder.columns = std::iter::once("time".to_string())
+        .chain(column_names)
+        .collect();
+    Ok(())
+}
+
+// adds a channel column unless its name is taken by the time, a state or another channel, which
+// would make the table invalid. SQLite compares column names case insensitively.
+fn add_column(column_names: &mut Vec<String>, column_name: String) -> bool {
+    let taken = column_name.eq_ignore_ascii_case("time")
+        || column_names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(&column_name));
+    if taken {
+        eprintln!(
+            "Recorder column \"{}\" is already recorded, skipping the channel",
+            column_name
+        );
+        return false;
+    }
+    column_names.push(column_name);
+    true
 }
 
-fn insert_data<T: Component + Stateful>(recorder: &mut Recorder, query: &Query<&T>, time: f32) {
+// quote a table or column name for SQL, so names with spaces or keywords work
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn insert_data<T: Component + Stateful>(
+    recorder: &mut Recorder,
+    world: &mut World,
+    time: f32,
+) -> rusqlite::Result<()> {
+    // not recording
+    let Some(insert_stmt) = recorder.insert_stmt.clone() else {
+        return Ok(());
+    };
     let mut sql_params = Vec::<f32>::new();
     sql_params.push(time);
-    for joint in query.iter() {
-        sql_params.push(joint.get_state().into());
-        sql_params.push(joint.get_dstate().into());
+    // the columns were fixed when the table was created, values of entities or components that were
+    // removed since are recorded as NaN
+    let physics_state = world.get_resource::<PhysicsState<T>>();
+    for (entity, channels) in recorder.entities.iter() {
+        let joint = world.get::<T>(*entity);
+        // the integrated states, the components hold whichever state was evaluated last
+        let state = physics_state
+            .and_then(|physics_state| physics_state.states.get(entity).cloned())
+            .or_else(|| joint.map(|joint| joint.get_state()));
+        let dstate = physics_state
+            .and_then(|physics_state| physics_state.dstates.get(entity).cloned())
+            .or_else(|| joint.map(|joint| joint.get_dstate()));
+        sql_params.push(state.map_or(f32::NAN, Into::into));
+        sql_params.push(dstate.map_or(f32::NAN, Into::into));
+        for index in channels.iter() {
+            if let Channel::Component { value, .. } = &recorder.channels[*index] {
+                sql_params.push(value(world, *entity).unwrap_or(f32::NAN));
+            }
+        }
+    }
+    for index in recorder.world_channels.iter() {
+        if let Channel::World { value, .. } = &recorder.channels[*index] {
+            sql_params.push(value(world));
+        }
+    }
+    recorder.last_row = sql_params.clone();
+    recorder.buffer.push_back(sql_params.clone());
+    while recorder.buffer.len() > recorder.buffer_length {
+        recorder.buffer.pop_front();
     }
     let sql_params: Vec<&dyn rusqlite::types::ToSql> = sql_params
         .iter()
         .map(|x| x as &dyn rusqlite::types::ToSql)
         .collect();
-    let temp = recorder.insert_stmt.clone().unwrap();
-    let mut stmt = recorder.conn.prepare(&temp).unwrap();
-    stmt.execute(sql_params.as_slice()).unwrap();
+    // a transaction per frame rather than per row, which would write the file every step
+    if recorder.conn.is_autocommit() {
+        recorder.conn.execute_batch("BEGIN")?;
+    }
+    let mut stmt = recorder.conn.prepare_cached(&insert_stmt)?;
+    stmt.execute(sql_params.as_slice())?;
+    Ok(())
 }
 
 pub fn create_recorder(world: &mut World) {
-    let folder_name = "./data";
-    let file_name = "dummy.db";
-    let full_path = format!("{}/{}", folder_name, file_name);
+    recorder_builder("dummy.db")(world)
+}
 
-    // create folder if it doesn't exist
-    if !std::path::Path::new(folder_name).exists() {
-        std::fs::create_dir(folder_name).unwrap();
-    }
+// record to a named file in the data folder, so several runs can be kept and compared
+pub fn recorder_builder(file_name: &str) -> impl Fn(&mut World) {
+    let file_name = file_name.to_string();
+    let create_recorder = move |world: &mut World| {
+        let folder_name = "./data";
+        let full_path = format!("{}/{}", folder_name, file_name);
 
-    let conn = Connection::open(full_path).unwrap();
+        // create folder if it doesn't exist
+        if !std::path::Path::new(folder_name).exists() {
+            std::fs::create_dir(folder_name).unwrap();
+        }
 
-    world.insert_non_send_resource(Recorder {
-        conn: conn,
-        insert_stmt: None,
-    });
+        let conn = Connection::open(full_path).unwrap();
 
-    println!("Recorder created")
+        world.insert_non_send_resource(Recorder::new(conn));
+
+        println!("Recorder created")
+    };
+    create_recorder
 }
 
 #[derive(Debug, Resource)]
@@ -121,60 +417,168 @@ pub struct RecordedData {
     pub data: HashMap<String, Vec<f32>>,
 }
 
-pub fn load_recorded_data(world: &mut World) {
-    let folder_name = "./data";
-    let file_name = "dummy.db";
-    let full_path = format!("{}/{}", folder_name, file_name);
-    let conn = Connection::open(full_path).unwrap();
-
-    let mut stmt = conn
-        .prepare("SELECT * FROM sqlite_master WHERE type='table'")
-        .unwrap();
-
-    let mut tables = stmt.query([]).unwrap();
-    let mut table_names = Vec::<String>::new();
-    let mut table_types = Vec::<String>::new();
-    while let Ok(table) = tables.next() {
-        if let Some(t) = table {
-            let name = t.get::<_, String>(1).unwrap();
-            let type_ = t.get::<_, String>(2).unwrap();
-            // println!("{}: {}", name, type_);
-            table_names.push(name);
-            table_types.push(type_);
-        } else {
-            break;
+impl RecordedData {
+    // value of a channel at time t, linearly interpolated between recorded rows
+    pub fn value_at(&self, channel: &str, t: f32) -> Option<f32> {
+        let times = self.data.get("time")?;
+        let values = self.data.get(channel)?;
+        if times.is_empty() || times.len() != values.len() {
+            return None;
         }
+        Some(interpolate(times, values, t))
+    }
+
+    pub fn time_range(&self) -> Option<(f32, f32)> {
+        let times = self.data.get("time")?;
+        Some((*times.first()?, *times.last()?))
+    }
+}
+
+// linear interpolation of recorded values, times must be sorted
+pub fn interpolate(times: &[f32], values: &[f32], t: f32) -> f32 {
+    let index = times.partition_point(|time| *time <= t);
+    if index == 0 {
+        return values[0];
     }
+    if index >= times.len() {
+        return values[times.len() - 1];
+    }
+    let (t0, t1) = (times[index - 1], times[index]);
+    let (v0, v1) = (values[index - 1], values[index]);
+    if t1 > t0 {
+        v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+    } else {
+        v0
+    }
+}
+
+pub fn load_recorded_data(world: &mut World) {
+    recorded_data_loader("dummy.db")(world)
+}
+
+pub fn recorded_data_loader(file_name: &str) -> impl Fn(&mut World) {
+    let file_name = file_name.to_string();
+    let load_recorded_data = move |world: &mut World| {
+        let folder_name = "./data";
+        let full_path = format!("{}/{}", folder_name, file_name);
+        let recorded_data = match read_recorded_data(&full_path) {
+            Ok(recorded_data) => recorded_data,
+            Err(error) => {
+                // an empty recording, so playback does nothing instead of panicking
+                eprintln!("Could not read recorded data from {}: {}", full_path, error);
+                RecordedData {
+                    data: HashMap::new(),
+                }
+            }
+        };
+
+        world.insert_resource(recorded_data);
+    };
+    load_recorded_data
+}
+
+// read the first table of a recorder database. The file is opened read only, so a mistyped path is
+// an error instead of a new empty database
+pub fn read_recorded_data(full_path: &str) -> rusqlite::Result<RecordedData> {
+    let conn = Connection::open_with_flags(full_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table'")?;
+    let table_names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    // a database without tables has nothing recorded
+    let table_name = table_names
+        .first()
+        .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
 
     // get data from first table and insert it into a hashmap
-    let stmt = conn
-        .prepare(format!("SELECT * FROM {}", table_names[0]).as_str())
-        .unwrap();
+    let table_name = quote_identifier(table_name);
+    let stmt = conn.prepare(format!("SELECT * FROM {}", table_name).as_str())?;
     let columns = stmt.column_names();
 
     let mut data = HashMap::<String, Vec<f32>>::new();
     for column in columns.iter() {
         // get data from the current column
         let mut col_stmt = conn
-            .prepare(format!("SELECT {} FROM {}", column, table_names[0]).as_str())
-            .unwrap();
-
-        // get data from rows
-        let mut rows = col_stmt.query([]).unwrap();
-
-        // loop through rows and add data to vector
-        let mut column_data = Vec::new();
-        while let Ok(row) = rows.next() {
-            if let Some(r) = row {
-                let value = r.get::<_, f32>(0).unwrap();
-                column_data.push(value);
-            } else {
-                break;
-            }
-        }
+            .prepare(format!("SELECT {} FROM {}", quote_identifier(column), table_name).as_str())?;
+        let column_data = col_stmt
+            // SQLite stores NaN as NULL, e.g. a component that was removed while recording
+            .query_map([], |row| {
+                Ok(row.get::<_, Option<f32>>(0)?.unwrap_or(f32::NAN))
+            })?
+            .collect::<rusqlite::Result<Vec<f32>>>()?;
 
         data.insert(column.to_string(), column_data);
     }
 
-    world.insert_resource(RecordedData { data: data });
+    Ok(RecordedData { data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Debug)]
+    struct Body {
+        x: f32,
+    }
+
+    impl Stateful for Body {
+        type State = f32;
+        fn get_state(&self) -> f32 {
+            self.x
+        }
+        fn set_state(&mut self, state: &f32) {
+            self.x = *state;
+        }
+        fn get_dstate(&self) -> f32 {
+            0.
+        }
+        fn set_dstate(&mut self, _dstate: f32) {}
+        fn reset(&mut self) {}
+        fn get_name(&self) -> String {
+            "body".to_string()
+        }
+    }
+
+    #[test]
+    fn channel_names_are_quoted_and_collisions_skipped() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<IntegratorStats>();
+        world.spawn(Body { x: 1.5 });
+        let mut recorder = Recorder::in_memory();
+        recorder
+            .add_world_channel("total energy", |_| 1.)
+            .add_world_channel("order", |_| 2.)
+            .add_world_channel("say \"hi\"", |_| 3.)
+            .add_world_channel("TIME", |_| 4.)
+            .add_channel("state", |body: &Body| body.x);
+        world.insert_non_send_resource(recorder);
+
+        initialize_recorder::<Body>(&mut world);
+        recorder_system::<Body>(&mut world);
+
+        let mut recorder = world.non_send_resource_mut::<Recorder>();
+        assert_eq!(
+            recorder.columns(),
+            [
+                "time",
+                "body_state",
+                "body_dstate",
+                "total energy",
+                "order",
+                "say \"hi\""
+            ]
+        );
+        assert_eq!(recorder.last_row(), [0., 1.5, 0., 1., 2., 3.]);
+        recorder.commit().unwrap();
+        let rows: i64 = recorder
+            .conn
+            .query_row("SELECT COUNT(\"total energy\") FROM data", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
 }
@@ -0,0 +1,357 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+};
+
+use bevy::prelude::*;
+
+use crate::recorder::Recorder;
+
+// Streams the channels captured by the recorder to external tools over a local TCP socket.
+// Each frame is a single line of JSON, e.g. {"time":1.25,"cube_state":2.71,"cube_dstate":-0.3}
+// Clients can send line based commands to the server:
+//   subscribe cube_state,cube_force   only stream these channels (time is always sent)
+//   subscribe *                       stream all channels (default)
+//   rate 30                           limit the stream to 30 frames per second
+// The server only listens on localhost.
+
+pub struct TelemetryPlugin {
+    pub port: u16,     // 0 picks a free port, see TelemetryServer::local_addr
+    pub max_rate: f32, // frames per second
+}
+
+impl Default for TelemetryPlugin {
+    fn default() -> Self {
+        TelemetryPlugin {
+            port: 7878,
+            max_rate: 60.,
+        }
+    }
+}
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, self.port));
+        // telemetry is optional, keep the app running without it if the port is taken
+        let listener = match TcpListener::bind(address)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+        {
+            Ok(listener) => listener,
+            Err(error) => {
+                eprintln!(
+                    "Telemetry server could not listen on {}: {}",
+                    address, error
+                );
+                return;
+            }
+        };
+        if let Ok(address) = listener.local_addr() {
+            println!("Telemetry server listening on {}", address);
+        }
+
+        app.insert_resource(TelemetryServer {
+            listener,
+            clients: Vec::new(),
+            max_rate: self.max_rate,
+        })
+        .add_system(telemetry_system.in_base_set(CoreSet::PostUpdate));
+    }
+}
+
+#[derive(Resource)]
+pub struct TelemetryServer {
+    listener: TcpListener,
+    clients: Vec<TelemetryClient>,
+    max_rate: f32,
+}
+
+struct TelemetryClient {
+    stream: TcpStream,
+    subscription: Option<Vec<String>>, // None streams all channels
+    rate: f32,
+    last_sent: f32,
+    received: String,
+    pending: Vec<u8>,
+    connected: bool,
+}
+
+// drop clients that stop reading rather than buffering frames forever
+const MAX_PENDING_BYTES: usize = 1 << 20;
+// drop clients that send a command without ending it, the commands are a few bytes long
+const MAX_RECEIVED_BYTES: usize = 1 << 16;
+
+impl TelemetryServer {
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    // the address clients connect to, with the actual port when the plugin was given port 0
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+pub fn telemetry_system(
+    mut server: ResMut<TelemetryServer>,
+    recorder: Option<NonSend<Recorder>>,
+    time: Res<Time>,
+) {
+    let server = &mut *server;
+
+    // accept new clients
+    while let Ok((stream, _)) = server.listener.accept() {
+        if stream.set_nonblocking(true).is_err() {
+            continue;
+        }
+        server.clients.push(TelemetryClient {
+            stream,
+            subscription: None,
+            rate: server.max_rate,
+            last_sent: f32::NEG_INFINITY,
+            received: String::new(),
+            pending: Vec::new(),
+            connected: true,
+        });
+    }
+
+    let now = time.raw_elapsed_seconds();
+    for client in server.clients.iter_mut() {
+        client.read_commands(server.max_rate);
+
+        // send the latest recorded row, rate limited per client
+        if let Some(recorder) = &recorder {
+            if !recorder.last_row().is_empty() && now - client.last_sent >= 1. / client.rate {
+                let frame = client.frame(recorder.columns(), recorder.last_row());
+                client.pending.extend_from_slice(frame.as_bytes());
+                client.last_sent = now;
+            }
+        }
+        client.flush();
+    }
+
+    server.clients.retain(|client| client.connected);
+}
+
+impl TelemetryClient {
+    fn read_commands(&mut self, max_rate: f32) {
+        let mut buffer = [0u8; 1024];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.connected = false;
+                    return;
+                }
+                Ok(n) => {
+                    self.received
+                        .push_str(&String::from_utf8_lossy(&buffer[..n]));
+                    self.handle_commands(max_rate);
+                    if self.received.len() > MAX_RECEIVED_BYTES {
+                        self.connected = false;
+                        return;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.connected = false;
+                    return;
+                }
+            }
+        }
+    }
+
+    // handle the complete lines received so far
+    fn handle_commands(&mut self, max_rate: f32) {
+        while let Some(end) = self.received.find('\n') {
+            let line: String = self.received.drain(..=end).collect();
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("subscribe"), Some("*")) => self.subscription = None,
+                (Some("subscribe"), Some(channels)) => {
+                    self.subscription = Some(channels.split(',').map(String::from).collect());
+                }
+                (Some("rate"), Some(rate)) => {
+                    if let Ok(rate) = rate.parse::<f32>() {
+                        if rate > 0. {
+                            self.rate = rate.min(max_rate);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn frame(&self, columns: &[String], row: &[f32]) -> String {
+        let mut frame = String::from("{");
+        for (column, value) in columns.iter().zip(row.iter()) {
+            let subscribed = match &self.subscription {
+                Some(channels) => column == "time" || channels.contains(column),
+                None => true,
+            };
+            if !subscribed {
+                continue;
+            }
+            if frame.len() > 1 {
+                frame.push(',');
+            }
+            // JSON has no representation for NaN or infinity
+            if value.is_finite() {
+                frame.push_str(&format!("\"{}\":{}", column, value));
+            } else {
+                frame.push_str(&format!("\"{}\":null", column));
+            }
+        }
+        frame.push_str("}\n");
+        frame
+    }
+
+    fn flush(&mut self) {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => {
+                    self.connected = false;
+                    return;
+                }
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.connected = false;
+                    return;
+                }
+            }
+        }
+        if self.pending.len() > MAX_PENDING_BYTES {
+            self.connected = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader},
+        ops::{Add, Mul},
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::{
+        integrator::Stateful,
+        recorder::{initialize_recorder, recorder_system, Recorder},
+    };
+
+    #[derive(Component, Debug)]
+    struct Body {
+        position: f32,
+        velocity: f32,
+    }
+
+    #[derive(Clone)]
+    struct BodyState(f32, f32);
+
+    impl Add for BodyState {
+        type Output = BodyState;
+        fn add(self, other: BodyState) -> BodyState {
+            BodyState(self.0 + other.0, self.1 + other.1)
+        }
+    }
+
+    impl Mul<f32> for BodyState {
+        type Output = BodyState;
+        fn mul(self, other: f32) -> BodyState {
+            BodyState(self.0 * other, self.1 * other)
+        }
+    }
+
+    impl From<BodyState> for f32 {
+        fn from(state: BodyState) -> f32 {
+            state.0
+        }
+    }
+
+    impl Stateful for Body {
+        type State = BodyState;
+        fn get_state(&self) -> BodyState {
+            BodyState(self.position, self.velocity)
+        }
+        fn set_state(&mut self, state: &BodyState) {
+            (self.position, self.velocity) = (state.0, state.1);
+        }
+        fn get_dstate(&self) -> BodyState {
+            BodyState(self.velocity, 0.)
+        }
+        fn set_dstate(&mut self, dstate: BodyState) {
+            self.velocity = dstate.0;
+        }
+        fn reset(&mut self) {}
+        fn get_name(&self) -> String {
+            "body".to_string()
+        }
+    }
+
+    // {"time":0,"body_state":1.5} -> {"time": 0, "body_state": 1.5}
+    fn parse_frame(line: &str) -> HashMap<String, f32> {
+        line.trim()
+            .trim_start_matches('{')
+            .trim_end_matches('}')
+            .split(',')
+            .filter_map(|field| {
+                let (name, value) = field.split_once(':')?;
+                Some((name.trim_matches('"').to_string(), value.parse().ok()?))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn client_receives_subscribed_channels() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins).add_plugin(TelemetryPlugin {
+            port: 0,
+            max_rate: 1000.,
+        });
+        app.world.insert_non_send_resource(Recorder::in_memory());
+        app.world.spawn(Body {
+            position: 1.5,
+            velocity: -2.,
+        });
+        initialize_recorder::<Body>(&mut app.world);
+        app.add_system(recorder_system::<Body>);
+
+        let address = app
+            .world
+            .resource::<TelemetryServer>()
+            .local_addr()
+            .unwrap();
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"subscribe body_state\n").unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let mut reader = BufReader::new(stream);
+
+        // frames sent before the subscription was read contain every channel
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut frame = None;
+        while frame.is_none() && Instant::now() < deadline {
+            app.update();
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) {
+                let fields = parse_frame(&line);
+                if !fields.contains_key("body_dstate") {
+                    frame = Some(fields);
+                    break;
+                }
+                line.clear();
+            }
+        }
+
+        let frame = frame.expect("no frame with only the subscribed channels");
+        assert_eq!(frame.len(), 2);
+        assert!(frame.contains_key("time"));
+        assert_eq!(frame["body_state"], 1.5);
+        assert_eq!(app.world.resource::<TelemetryServer>().client_count(), 1);
+    }
+}
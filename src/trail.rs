@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{render_resource::PrimitiveTopology, view::NoFrustumCulling},
+    transform::TransformSystem,
+};
+
+use crate::{integrator::IntegratorStats, playback::Playback};
+
+// Motion trails of entities, sampled at the simulation time (the playback time when replaying,
+// otherwise the integrator time like the recorder). A trail keeps the positions of the last
+// `length` seconds and is drawn as a polyline fading out towards its oldest point.
+// Trails are cleared when the simulation time goes backwards (e.g. stepping back a replay), when
+// the integrator is reset (IntegratorStats::reset), or when a ClearTrails event is sent.
+
+pub struct TrailPlugin;
+
+impl Plugin for TrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ClearTrails>()
+            .add_system(
+                update_trails
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(TransformSystem::TransformPropagate),
+            )
+            .add_system(
+                draw_trails
+                    .in_base_set(CoreSet::PostUpdate)
+                    .after(update_trails),
+            );
+    }
+}
+
+// clear all trails, e.g. after resetting the simulation
+pub struct ClearTrails;
+
+// color the trail from `slow` at rest to `fast` at `max_speed` and above
+#[derive(Clone, Copy, Debug)]
+pub struct SpeedColors {
+    pub slow: Color,
+    pub fast: Color,
+    pub max_speed: f32, // m/s
+}
+
+#[derive(Component)]
+pub struct Trail {
+    pub length: f32, // seconds
+    pub max_points: usize,
+    pub color: Color,
+    pub speed_colors: Option<SpeedColors>,
+    points: VecDeque<(f32, Vec3)>, // (time, position), oldest first
+    mesh: Option<Entity>,
+}
+
+impl Trail {
+    pub fn new(length: f32) -> Self {
+        Trail {
+            length,
+            max_points: 1000,
+            color: Color::WHITE,
+            speed_colors: None,
+            points: VecDeque::new(),
+            mesh: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_speed_colors(mut self, speed_colors: SpeedColors) -> Self {
+        self.speed_colors = Some(speed_colors);
+        self
+    }
+
+    pub fn with_max_points(mut self, max_points: usize) -> Self {
+        self.max_points = max_points;
+        self
+    }
+
+    pub fn points(&self) -> &VecDeque<(f32, Vec3)> {
+        &self.points
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    fn add_point(&mut self, time: f32, position: Vec3) {
+        if let Some((last_time, _)) = self.points.back() {
+            if time < *last_time {
+                self.points.clear(); // rewound
+            } else if time == *last_time {
+                return; // paused
+            }
+        }
+        self.points.push_back((time, position));
+        while self.points.len() > self.max_points.max(2)
+            || self
+                .points
+                .front()
+                .is_some_and(|(t, _)| *t < time - self.length)
+        {
+            self.points.pop_front();
+        }
+    }
+
+    // colored line segments, fading from opaque at the newest point to transparent at the oldest
+    fn mesh_data(&self) -> (Vec<[f32; 3]>, Vec<[f32; 4]>) {
+        let mut positions = Vec::new();
+        let mut colors = Vec::new();
+        let Some((newest, _)) = self.points.back() else {
+            return (positions, colors);
+        };
+        let alpha = |t: f32| (1. - (newest - t) / self.length).clamp(0., 1.);
+        for ((t0, p0), (t1, p1)) in self.points.iter().zip(self.points.iter().skip(1)) {
+            let color = match self.speed_colors {
+                Some(speed_colors) => {
+                    let speed = (*p1 - *p0).length() / (t1 - t0).max(f32::EPSILON);
+                    let s = (speed / speed_colors.max_speed).clamp(0., 1.);
+                    let slow = Vec4::from(speed_colors.slow.as_rgba_f32());
+                    let fast = Vec4::from(speed_colors.fast.as_rgba_f32());
+                    slow.lerp(fast, s)
+                }
+                None => Vec4::from(self.color.as_rgba_f32()),
+            };
+            for (t, p) in [(t0, p0), (t1, p1)] {
+                positions.push(p.to_array());
+                colors.push([color.x, color.y, color.z, color.w * alpha(*t)]);
+            }
+        }
+        (positions, colors)
+    }
+}
+
+// line mesh drawing the trail of an entity
+#[derive(Component)]
+pub struct TrailMesh(pub Entity);
+
+fn simulation_time(
+    time: &Time,
+    stats: Option<&IntegratorStats>,
+    playback: Option<&Playback>,
+) -> f32 {
+    match (playback, stats) {
+        (Some(playback), _) => playback.time,
+        (None, Some(stats)) => stats.time(),
+        (None, None) => time.elapsed_seconds(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_trails(
+    mut commands: Commands,
+    time: Res<Time>,
+    stats: Option<Res<IntegratorStats>>,
+    playback: Option<Res<Playback>>,
+    mut ev_clear: EventReader<ClearTrails>,
+    mut last_resets: Local<u64>,
+    mut trails: Query<(Entity, &mut Trail, &GlobalTransform)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let resets = stats.as_ref().map_or(0, |stats| stats.resets);
+    let reset = resets != *last_resets;
+    *last_resets = resets;
+    let clear = ev_clear.iter().count() > 0 || reset;
+    let now = simulation_time(&time, stats.as_deref(), playback.as_deref());
+    for (entity, mut trail, transform) in trails.iter_mut() {
+        if clear {
+            trail.clear();
+        }
+        trail.add_point(now, transform.translation());
+
+        if trail.mesh.is_none() {
+            let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0_f32; 3]; 2]);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![[0_f32; 4]; 2]);
+            let mesh_entity = commands
+                .spawn((
+                    PbrBundle {
+                        mesh: meshes.add(mesh),
+                        material: materials.add(StandardMaterial {
+                            base_color: Color::WHITE, // multiplied with the vertex colors
+                            unlit: true,
+                            alpha_mode: AlphaMode::Blend,
+                            ..default()
+                        }),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    TrailMesh(entity),
+                    NotShadowCaster,
+                    NoFrustumCulling, // the trail changes every frame, so the bounds are never up to date
+                ))
+                .id();
+            trail.mesh = Some(mesh_entity);
+        }
+    }
+}
+
+pub fn draw_trails(
+    mut commands: Commands,
+    trails: Query<&Trail>,
+    mut trail_meshes: Query<(Entity, &TrailMesh, &Handle<Mesh>, &mut Visibility)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (entity, trail_mesh, mesh, mut visibility) in trail_meshes.iter_mut() {
+        let Ok(trail) = trails.get(trail_mesh.0) else {
+            // the entity or its trail was removed
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let (positions, colors) = trail.mesh_data();
+        if positions.is_empty() {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        }
+    }
+}